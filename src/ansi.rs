@@ -18,12 +18,15 @@
 // The same applies to index.rs
 
 //! ANSI Terminal Stream Parsing
+use std::ffi::OsString;
 use std::io;
 use std::ops::Range;
+use std::path::PathBuf;
 use std::str;
 
 use vte;
 
+use clipboard::decode_base64;
 use index::{Column, Contains, Line};
 
 macro_rules! warn {
@@ -45,15 +48,180 @@ pub struct Processor {
     parser: vte::Parser,
 }
 
+/// Maximum number of CSI parameters that vte collects
+const MAX_CSI_PARAMS: usize = 16;
+
+/// Maximum length of OSC strings (longer ones are dropped)
+pub const MAX_OSC_LEN: usize = 4 << 20;
+
+/// Maximum length of the data of handled DCS strings (longer ones are dropped)
+pub const MAX_DCS_LEN: usize = 4 << 10;
+
 /// Internal state for VTE processor
-struct ProcessorState;
+struct ProcessorState {
+    /// Where we are in the current escape sequence (as far as colon preprocessing is concerned)
+    sequence: SequenceState,
+    /// Index of the CSI parameter that is currently being parsed
+    param: usize,
+    /// Whether a CSI parameter was separated from its predecessor by a colon, i.e., is a
+    /// sub-parameter
+    subparams: [bool; MAX_CSI_PARAMS],
+    /// Contents of the current OSC string
+    osc: Vec<u8>,
+    /// Whether the current OSC string exceeds MAX_OSC_LEN
+    osc_overflow: bool,
+    /// How the current OSC string has been terminated
+    osc_terminator: OscTerminator,
+    /// Final byte of the current DCS header (which vte does not pass on to `hook`)
+    dcs_final: u8,
+    /// The request of the current DCS string (if it is handled at all)
+    dcs_request: Option<DcsRequest>,
+    /// Data of the current DCS string
+    dcs: Vec<u8>,
+    /// Whether the data of the current DCS string exceeds MAX_DCS_LEN
+    dcs_overflow: bool,
+}
+
+/// DCS strings that are handled
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DcsRequest {
+    /// DECRQSS (`DCS $ q Pt ST`)
+    StatusString,
+    /// XTGETTCAP (`DCS + q Pt ST`)
+    Termcap,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SequenceState {
+    Ground,
+    Escape,
+    CsiParams,
+    Osc,
+    DcsHeader,
+    Dcs,
+}
+
+impl Default for ProcessorState {
+    fn default() -> Self {
+        ProcessorState {
+            sequence: SequenceState::Ground,
+            param: 0,
+            subparams: [false; MAX_CSI_PARAMS],
+            osc: Vec::new(),
+            osc_overflow: false,
+            osc_terminator: OscTerminator::Bel,
+            dcs_final: 0,
+            dcs_request: None,
+            dcs: Vec::new(),
+            dcs_overflow: false,
+        }
+    }
+}
+
+impl ProcessorState {
+    /// vte does not know about colon separated sub-parameters (as used in, e.g., `CSI 4:3 m`) and
+    /// ignores sequences that contain them. We therefore replace colons in CSI parameters with
+    /// semicolons before passing them on to vte, but remember which parameters were
+    /// sub-parameters.
+    ///
+    /// Moreover, vte truncates OSC strings to 1024 bytes, which is not enough for, e.g., clipboard
+    /// contents, so we collect them ourselves.
+    fn preprocess(&mut self, byte: u8) -> u8 {
+        match self.sequence {
+            SequenceState::Ground => {
+                if byte == 0x1b {
+                    self.sequence = SequenceState::Escape;
+                }
+            }
+            SequenceState::Escape => {
+                self.sequence = match byte {
+                    b'[' => {
+                        self.param = 0;
+                        self.subparams = [false; MAX_CSI_PARAMS];
+                        SequenceState::CsiParams
+                    }
+                    b']' => {
+                        self.osc.clear();
+                        self.osc_overflow = false;
+                        SequenceState::Osc
+                    }
+                    b'P' => SequenceState::DcsHeader,
+                    0x1b => SequenceState::Escape,
+                    _ => SequenceState::Ground,
+                }
+            }
+            SequenceState::CsiParams => match byte {
+                b':' => {
+                    self.param += 1;
+                    if let Some(subparam) = self.subparams.get_mut(self.param) {
+                        *subparam = true;
+                    }
+                    return b';';
+                }
+                b';' => self.param += 1,
+                b'0'..=b'9' | b'<'..=b'?' => {}
+                0x1b => self.sequence = SequenceState::Escape,
+                // Other C0 controls are executed without leaving the sequence.
+                0x00..=0x17 | 0x19 | 0x1c..=0x1f => {}
+                _ => self.sequence = SequenceState::Ground,
+            },
+            SequenceState::Osc => match byte {
+                // BEL or the start of ST terminate the string, CAN and SUB abort it.
+                0x07 => {
+                    self.osc_terminator = OscTerminator::Bel;
+                    self.sequence = SequenceState::Ground;
+                }
+                0x18 | 0x1a => self.sequence = SequenceState::Ground,
+                0x1b => {
+                    self.osc_terminator = OscTerminator::St;
+                    self.sequence = SequenceState::Escape;
+                }
+                // Other C0 controls are ignored.
+                0x00..=0x1f => {}
+                _ if self.osc.len() >= MAX_OSC_LEN => self.osc_overflow = true,
+                _ => self.osc.push(byte),
+            },
+            SequenceState::DcsHeader => match byte {
+                0x40..=0x7e => {
+                    self.dcs_final = byte;
+                    self.sequence = SequenceState::Dcs;
+                }
+                0x1b => self.sequence = SequenceState::Escape,
+                0x18 | 0x1a => self.sequence = SequenceState::Ground,
+                _ => {}
+            },
+            // The data itself is collected in `Performer::put`.
+            SequenceState::Dcs => match byte {
+                0x1b => self.sequence = SequenceState::Escape,
+                0x18 | 0x1a => self.sequence = SequenceState::Ground,
+                _ => {}
+            },
+        }
+        byte
+    }
+
+    /// Get the complete contents of the OSC string that has just been terminated (or `None` if it
+    /// is too long).
+    fn take_osc(&mut self) -> Option<Vec<u8>> {
+        if self.osc_overflow {
+            None
+        } else {
+            Some(::std::mem::take(&mut self.osc))
+        }
+    }
+
+    /// Check whether the CSI parameter with the given index is a sub-parameter of its predecessor.
+    fn is_subparam(&self, index: usize) -> bool {
+        self.subparams.get(index).cloned().unwrap_or(false)
+    }
+}
 
 /// Helper type that implements `vte::Perform`.
 ///
 /// Processor creates a Performer when running advance and passes the Performer
 /// to `vte::Parser`.
 struct Performer<'a, H: Handler + TermInfo + 'a, W: io::Write + 'a> {
-    _state: &'a mut ProcessorState,
+    state: &'a mut ProcessorState,
     handler: &'a mut H,
     writer: &'a mut W,
 }
@@ -67,7 +235,7 @@ impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
         writer: &'b mut W,
     ) -> Performer<'b, H, W> {
         Performer {
-            _state: state,
+            state,
             handler,
             writer,
         }
@@ -77,7 +245,7 @@ impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
 impl Default for Processor {
     fn default() -> Processor {
         Processor {
-            state: ProcessorState,
+            state: ProcessorState::default(),
             parser: vte::Parser::new(),
         }
     }
@@ -94,6 +262,7 @@ impl Processor {
         H: Handler + TermInfo,
         W: io::Write,
     {
+        let byte = self.state.preprocess(byte);
         let mut performer = Performer::new(&mut self.state, handler, writer);
         self.parser.advance(&mut performer, byte);
     }
@@ -110,8 +279,14 @@ pub trait TermInfo {
 /// XXX Should probably not provide default impls for everything, but it makes
 /// writing specific handler impls for tests far easier.
 pub trait Handler {
-    /// OSC to set window title
-    fn set_title(&mut self, _: &str) {}
+    /// OSC to set window title and/or icon name
+    fn set_title(&mut self, _: TitleKind, _: &str) {}
+
+    /// Save the window title and/or icon name on the title stack
+    fn push_title(&mut self, _: TitleKind) {}
+
+    /// Restore the window title and/or icon name from the title stack
+    fn pop_title(&mut self, _: TitleKind) {}
 
     /// Set the cursor style
     fn set_cursor_style(&mut self, _: CursorStyle) {}
@@ -170,8 +345,6 @@ pub trait Handler {
     fn linefeed(&mut self) {}
 
     /// Ring the bell
-    ///
-    /// Hopefully this is never implemented
     fn bell(&mut self) {}
 
     /// Substitute char under cursor
@@ -247,6 +420,10 @@ pub trait Handler {
     /// Unset mode
     fn unset_mode(&mut self, _: Mode) {}
 
+    /// DECRQM - Report the state of the (private or ANSI) mode with the given number, which may
+    /// not be a known `Mode` (should write back to the pty stream)
+    fn report_mode<W: io::Write>(&mut self, _: &mut W, _private: bool, _: i64) {}
+
     /// DECSTBM - Set the terminal scrolling region
     fn set_scrolling_region(&mut self, _: Range<Line>) {}
 
@@ -269,8 +446,66 @@ pub trait Handler {
     fn configure_charset(&mut self, _: CharsetIndex, _: StandardCharset) {}
 
     /// Set an indexed color value
+    ///
+    /// Indices from 256 on refer to the dynamic colors as enumerated in `NamedColor`.
     fn set_color(&mut self, _: usize, _: Rgb) {}
 
+    /// Reset an indexed color to its default value (or all of the 256 indexed colors, if no index
+    /// is given)
+    fn reset_color(&mut self, _: Option<usize>) {}
+
+    /// Report an indexed (or dynamic) color value (should write back to the pty stream)
+    ///
+    /// The reply should be terminated like the request.
+    fn report_color<W: io::Write>(&mut self, _: &mut W, _: usize, _: OscTerminator) {}
+
+    /// OSC 8 to start (or end with `None`) a hyperlink for subsequently written cells
+    fn set_hyperlink(&mut self, _: Option<Hyperlink>) {}
+
+    /// OSC 52 to set the contents of the given selections
+    fn set_clipboard(&mut self, _: &[ClipboardSelection], _: &[u8]) {}
+
+    /// OSC 52 to query the contents of the given selection (should eventually write back to the
+    /// pty stream)
+    fn request_clipboard(&mut self, _: ClipboardSelection) {}
+
+    /// OSC 133 to mark the current position as part of a shell prompt or command
+    fn set_prompt_mark(&mut self, _: PromptMark) {}
+
+    /// OSC 9 or OSC 777 to show a desktop notification
+    fn notify(&mut self, _title: Option<&str>, _body: &str) {}
+
+    /// OSC 7 to report the current working directory (on the given host)
+    fn set_working_directory(&mut self, _: WorkingDirectory) {}
+
+    /// Push flags of the kitty keyboard protocol onto the stack of the current screen
+    fn push_keyboard_mode(&mut self, _: u8) {}
+
+    /// Pop the given number of flags of the kitty keyboard protocol from the stack
+    fn pop_keyboard_modes(&mut self, _: usize) {}
+
+    /// Modify the current flags of the kitty keyboard protocol
+    fn set_keyboard_mode(&mut self, _: u8, _: KeyboardModesApplyBehavior) {}
+
+    /// Report the current flags of the kitty keyboard protocol (should write back to the pty
+    /// stream)
+    fn report_keyboard_mode<W: io::Write>(&mut self, _: &mut W) {}
+
+    /// Set the modifyOtherKeys level of xterm
+    fn set_modify_other_keys(&mut self, _: ModifyOtherKeys) {}
+
+    /// Report the size of the window (should write back to the pty stream)
+    fn report_window_size<W: io::Write>(&mut self, _: &mut W, _: WindowSizeReport) {}
+
+    /// DECRQSS - Report the value of a setting (should write back to the pty stream)
+    ///
+    /// `None` denotes a setting that is not supported, which should be answered as invalid.
+    fn report_status_string<W: io::Write>(&mut self, _: &mut W, _: Option<StatusString>) {}
+
+    /// XTGETTCAP - Report the value of the terminfo capability with the given name (should write
+    /// back to the pty stream)
+    fn report_termcap<W: io::Write>(&mut self, _: &mut W, _: &str) {}
+
     /// Run the dectest routine
     fn dectest(&mut self) {}
 }
@@ -288,6 +523,150 @@ pub enum CursorStyle {
     Beam,
 }
 
+/// How flags of the kitty keyboard protocol are applied to the current ones (`CSI = flags ; mode u`)
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum KeyboardModesApplyBehavior {
+    /// Replace the current flags
+    Replace,
+    /// Set the given flags in addition to the current ones
+    Union,
+    /// Unset the given flags
+    Difference,
+}
+
+/// Levels of the modifyOtherKeys mode of xterm (`CSI > 4 ; level m`)
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub enum ModifyOtherKeys {
+    /// Modified keys are sent as usual
+    #[default]
+    Reset,
+    /// Modified keys without well-known encodings are reported as `CSI 27 ; mod ; key ~`
+    EnableExceptWellDefined,
+    /// All modified keys are reported as `CSI 27 ; mod ; key ~`
+    EnableAll,
+}
+
+/// A hyperlink as specified using OSC 8
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Hyperlink {
+    /// Explicit id of the link: Cells with the same id and URI belong to the same link, even if
+    /// they are not adjacent.
+    pub id: Option<String>,
+    /// Target of the link
+    pub uri: String,
+}
+
+/// A working directory as reported using OSC 7
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WorkingDirectory {
+    /// Name of the host that the directory resides on (may be empty for the local host)
+    pub host: String,
+    /// (Absolute) path of the directory
+    pub path: PathBuf,
+}
+
+impl WorkingDirectory {
+    /// Check whether the directory resides on the machine that this program is running on, i.e.,
+    /// the host is empty, "localhost" or the name of this machine.
+    pub fn is_local(&self) -> bool {
+        if self.host.is_empty() || self.host == "localhost" {
+            return true;
+        }
+        let mut buffer = [0u8; 256];
+        match ::nix::unistd::gethostname(&mut buffer) {
+            Ok(hostname) => hostname.to_bytes() == self.host.as_bytes(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Semantic marks of shell integration (OSC 133)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PromptMark {
+    /// Start of the prompt (`A`)
+    PromptStart,
+    /// Start of the command, i.e., the end of the prompt (`B`)
+    CommandStart,
+    /// Start of the output of the command (`C`)
+    OutputStart,
+    /// End of the output of the command with its exit code, if known (`D`)
+    CommandFinished(Option<i32>),
+}
+
+/// Selections that can be accessed using OSC 52
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClipboardSelection {
+    /// The clipboard (`c`)
+    Clipboard,
+    /// The primary selection (`p`)
+    Primary,
+    /// The "selection" (`s`), which is configurable in xterm and usually means the primary
+    /// selection
+    Select,
+}
+
+impl ClipboardSelection {
+    /// Get the character that identifies the selection in OSC 52.
+    pub fn to_char(self) -> char {
+        match self {
+            ClipboardSelection::Clipboard => 'c',
+            ClipboardSelection::Primary => 'p',
+            ClipboardSelection::Select => 's',
+        }
+    }
+}
+
+/// Selects the window title and/or icon name
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum TitleKind {
+    /// Both icon name and window title
+    Both,
+    /// Only the icon name
+    IconName,
+    /// Only the window title
+    Title,
+}
+
+/// Window size reports that can be requested using XTWINOPS (`CSI Ps t`)
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum WindowSizeReport {
+    /// Size of the text area in pixels (`CSI 14 t`)
+    TextAreaPixels,
+    /// Size of a character cell in pixels (`CSI 16 t`)
+    CellPixels,
+    /// Size of the text area in characters (`CSI 18 t`)
+    TextAreaCells,
+}
+
+/// Terminator of an OSC string
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum OscTerminator {
+    /// BEL (`\x07`)
+    Bel,
+    /// ST (`ESC \\`)
+    St,
+}
+
+impl OscTerminator {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OscTerminator::Bel => "\x07",
+            OscTerminator::St => "\x1b\\",
+        }
+    }
+}
+
+/// Settings that can be queried using DECRQSS (`DCS $ q Pt ST`)
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum StatusString {
+    /// The current graphic rendition (`Pt` = `m`)
+    GraphicRendition,
+    /// The scrolling region set by DECSTBM (`Pt` = `r`)
+    ScrollingRegion,
+    /// The cursor style set by DECSCUSR (`Pt` = ` q`)
+    CursorStyle,
+}
+
 /// Terminal modes
 #[derive(Debug, Eq, PartialEq)]
 pub enum Mode {
@@ -316,6 +695,8 @@ pub enum Mode {
     Origin = 6,
     /// ?7
     LineWrap = 7,
+    /// ?9
+    X10Mouse = 9,
     /// ?12
     BlinkingCursor = 12,
     /// 20
@@ -329,14 +710,20 @@ pub enum Mode {
     ReportMouseClicks = 1000,
     /// ?1002
     ReportMouseMotion = 1002,
+    /// ?1003
+    ReportAllMouseMotion = 1003,
     /// ?1004
     ReportFocusInOut = 1004,
     /// ?1006
     SgrMouse = 1006,
+    /// ?1015
+    UrxvtMouse = 1015,
     /// ?1049
     SwapScreenAndSetRestoreCursor = 1049,
     /// ?2004
     BracketedPaste = 2004,
+    /// ?2026
+    SynchronizedOutput = 2026,
 }
 
 impl Mode {
@@ -350,14 +737,18 @@ impl Mode {
                 3 => Mode::DECCOLM,
                 6 => Mode::Origin,
                 7 => Mode::LineWrap,
+                9 => Mode::X10Mouse,
                 12 => Mode::BlinkingCursor,
                 25 => Mode::ShowCursor,
                 1000 => Mode::ReportMouseClicks,
                 1002 => Mode::ReportMouseMotion,
+                1003 => Mode::ReportAllMouseMotion,
                 1004 => Mode::ReportFocusInOut,
                 1006 => Mode::SgrMouse,
+                1015 => Mode::UrxvtMouse,
                 1049 => Mode::SwapScreenAndSetRestoreCursor,
                 2004 => Mode::BracketedPaste,
+                2026 => Mode::SynchronizedOutput,
                 _ => return None,
             })
         } else {
@@ -370,6 +761,21 @@ impl Mode {
     }
 }
 
+/// State of a mode as reported by DECRPM (`CSI ? Ps ; Pm $ y`)
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ModeState {
+    /// The mode is unknown or not supported
+    NotRecognized = 0,
+    /// The mode is currently set
+    Set = 1,
+    /// The mode is currently reset
+    Reset = 2,
+    /// The mode is always set and cannot be reset
+    PermanentlySet = 3,
+    /// The mode is always reset and cannot be set
+    PermanentlyReset = 4,
+}
+
 /// Mode for clearing line
 ///
 /// Relative to cursor
@@ -544,6 +950,14 @@ pub enum Attr {
     Italic,
     /// Underscore text
     Underscore,
+    /// Double underlined text
+    DoubleUnderline,
+    /// Curly underlined text
+    Undercurl,
+    /// Dotted underlined text
+    DottedUnderline,
+    /// Dashed underlined text
+    DashedUnderline,
     /// Blink cursor slowly
     BlinkSlow,
     /// Blink cursor fast
@@ -574,6 +988,8 @@ pub enum Attr {
     Foreground(Color),
     /// Set indexed background color
     Background(Color),
+    /// Set (or reset to the foreground color with `None`) the color of underlines
+    UnderlineColor(Option<Color>),
 }
 
 /// Identifiers which can be assigned to a graphic character set
@@ -635,25 +1051,86 @@ where
 
     #[inline]
     fn hook(&mut self, params: &[i64], intermediates: &[u8], ignore: bool) {
-        debug!(
-            "[unhandled hook] params={:?}, ints: {:?}, ignore: {:?}",
-            params, intermediates, ignore
-        );
+        let request = match (intermediates, self.state.dcs_final, ignore) {
+            (b"$", b'q', false) => Some(DcsRequest::StatusString),
+            (b"+", b'q', false) => Some(DcsRequest::Termcap),
+            _ => {
+                debug!(
+                    "[unhandled hook] params={:?}, ints: {:?}, final: {:?}, ignore: {:?}",
+                    params, intermediates, self.state.dcs_final as char, ignore
+                );
+                None
+            }
+        };
+        self.state.dcs_request = request;
+        self.state.dcs.clear();
+        self.state.dcs_overflow = false;
     }
 
     #[inline]
     fn put(&mut self, byte: u8) {
-        debug!("[unhandled put] byte={:?}", byte);
+        if self.state.dcs_request.is_none() {
+            return;
+        }
+        if self.state.dcs.len() >= MAX_DCS_LEN {
+            self.state.dcs_overflow = true;
+        } else {
+            self.state.dcs.push(byte);
+        }
     }
 
     #[inline]
     fn unhook(&mut self) {
-        debug!("[unhandled unhook]");
+        let request = match self.state.dcs_request.take() {
+            Some(request) => request,
+            None => return,
+        };
+        if self.state.dcs_overflow {
+            warn!("[unhook] Dropping DCS longer than {} bytes", MAX_DCS_LEN);
+            return;
+        }
+        let data = ::std::mem::take(&mut self.state.dcs);
+        match request {
+            DcsRequest::StatusString => {
+                let setting = match &data[..] {
+                    b"m" => Some(StatusString::GraphicRendition),
+                    b"r" => Some(StatusString::ScrollingRegion),
+                    b" q" => Some(StatusString::CursorStyle),
+                    _ => {
+                        debug!("[unhook] Unsupported DECRQSS {:?}", data);
+                        None
+                    }
+                };
+                self.handler.report_status_string(self.writer, setting);
+            }
+            DcsRequest::Termcap => {
+                for name in data.split(|b| *b == b';') {
+                    match decode_hex(name).and_then(|name| String::from_utf8(name).ok()) {
+                        Some(name) => self.handler.report_termcap(self.writer, &name),
+                        None => warn!("[unhook] Invalid XTGETTCAP name {:?}", name),
+                    }
+                }
+            }
+        }
     }
 
     // TODO replace OSC parsing with parser combinators
     #[inline]
-    fn osc_dispatch(&mut self, params: &[&[u8]]) {
+    fn osc_dispatch(&mut self, _truncated_params: &[&[u8]]) {
+        // vte truncates long OSC strings, so we use our own copy (see ProcessorState::preprocess).
+        let osc = match self.state.take_osc() {
+            Some(osc) => osc,
+            None => {
+                warn!(
+                    "[osc_dispatch] Dropping OSC longer than {} bytes",
+                    MAX_OSC_LEN
+                );
+                return;
+            }
+        };
+        let params = osc.split(|b| *b == b';').collect::<Vec<_>>();
+        let params = &params[..];
+
         macro_rules! unhandled {
             () => {{
                 let mut buf = String::new();
@@ -672,108 +1149,223 @@ where
             return;
         }
 
-        match params[0][0] {
-            // Set window title
-            b'0' | b'2' => {
+        match params[0] {
+            // Set icon name and/or window title
+            b"0" | b"1" | b"2" => {
                 if params.len() < 2 {
                     return unhandled!();
                 }
 
-                if let Ok(utf8_title) = str::from_utf8(params[1]) {
-                    self.handler.set_title(utf8_title);
+                // The title itself may contain the separator.
+                let title = params[1..].join(&b';');
+                let kind = match params[0] {
+                    b"0" => TitleKind::Both,
+                    b"1" => TitleKind::IconName,
+                    _ => TitleKind::Title,
+                };
+                if let Ok(utf8_title) = str::from_utf8(&title) {
+                    self.handler.set_title(kind, utf8_title);
                 }
             }
 
-            // Set icon name
-            // This is ignored, since alacritty has no concept of tabs
-            b'1' => return,
+            // Report the current working directory as a file URL
+            b"7" => {
+                if params.len() < 2 {
+                    return unhandled!();
+                }
+
+                match parse_file_url(params[1]) {
+                    Some((host, path)) => self
+                        .handler
+                        .set_working_directory(WorkingDirectory { host, path }),
+                    None => unhandled!(),
+                }
+            }
 
-            // Set color index
-            b'4' => {
+            // Start or end a hyperlink: "8;params;uri", where params are of the form "key=value"
+            // separated by ':'. An empty URI ends the link.
+            b"8" => {
                 if params.len() < 3 {
                     return unhandled!();
                 }
 
-                // Parse index
-                let index = {
-                    let raw = params[1];
-                    if raw.len() > 3 {
-                        return unhandled!();
-                    }
-                    let mut index: u8 = 0;
-                    for c in raw {
-                        let c = *c as char;
-                        if let Some(digit) = c.to_digit(10) {
-                            index *= 10;
-                            index += digit as u8;
-                        }
-                    }
-
-                    index
+                // The URI itself may contain the separator.
+                let uri = params[2..].join(&b';');
+                let uri = match str::from_utf8(&uri) {
+                    Ok(uri) => uri,
+                    Err(_) => return unhandled!(),
                 };
+                if uri.is_empty() {
+                    self.handler.set_hyperlink(None);
+                    return;
+                }
+                let id = params[1]
+                    .split(|b| *b == b':')
+                    .filter_map(|param| param.strip_prefix(b"id="))
+                    .find(|id| !id.is_empty())
+                    .map(|id| String::from_utf8_lossy(id).into_owned());
+                self.handler.set_hyperlink(Some(Hyperlink {
+                    id,
+                    uri: uri.to_owned(),
+                }));
+            }
+
+            // Set or query the clipboard: "52;selections;data", where data is "?" for a query or
+            // base64 encoded.
+            b"52" => {
+                if params.len() < 3 {
+                    return unhandled!();
+                }
 
-                // Check that index is a valid u8
-                if index & 0xff != index {
+                // Other selections (cut buffers, secondary selection) are not supported.
+                let mut selections = params[1]
+                    .iter()
+                    .filter_map(|b| match b {
+                        b'c' => Some(ClipboardSelection::Clipboard),
+                        b'p' => Some(ClipboardSelection::Primary),
+                        b's' => Some(ClipboardSelection::Select),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                if params[1].is_empty() {
+                    selections.push(ClipboardSelection::Clipboard);
+                }
+                if selections.is_empty() {
                     return unhandled!();
                 }
 
-                // Parse color arguments
-                //
-                // Expect that color argument looks like "rgb:xx/xx/xx"
-                let color = {
-                    let raw = params[2];
-                    let mut iter = raw.iter();
-                    macro_rules! next {
-                        () => {
-                            iter.next().map(|v| *v as char)
-                        };
-                    }
-                    if next!() != Some('r') {
-                        return unhandled!();
-                    }
-                    if next!() != Some('g') {
-                        return unhandled!();
-                    }
-                    if next!() != Some('b') {
-                        return unhandled!();
-                    }
-                    if next!() != Some(':') {
-                        return unhandled!();
-                    }
+                if params[2] == b"?" {
+                    self.handler.request_clipboard(selections[0]);
+                } else if let Some(data) = decode_base64(params[2]) {
+                    self.handler.set_clipboard(&selections, &data);
+                } else {
+                    // The data may be large, so we do not print it.
+                    warn!("[osc_dispatch] Invalid base64 data in OSC 52");
+                }
+            }
 
-                    macro_rules! parse_hex {
-                        () => {{
-                            let mut digit: u8 = 0;
-                            let next = next!().and_then(|v| v.to_digit(16));
-                            if let Some(value) = next {
-                                digit = value as u8;
-                            }
+            // Shell integration: "133;mark[;args]", where only D (command finished) has a
+            // meaningful argument (the exit code). Other arguments are of the form "key=value".
+            b"133" => {
+                if params.len() < 2 {
+                    return unhandled!();
+                }
 
-                            let next = next!().and_then(|v| v.to_digit(16));
-                            if let Some(value) = next {
-                                digit <<= 4;
-                                digit += value as u8;
-                            }
-                            digit
-                        }};
+                let mark = match params[1] {
+                    b"A" => PromptMark::PromptStart,
+                    b"B" => PromptMark::CommandStart,
+                    b"C" => PromptMark::OutputStart,
+                    b"D" => {
+                        let exit_code = params
+                            .get(2)
+                            .and_then(|code| str::from_utf8(code).ok())
+                            .and_then(|code| code.parse().ok());
+                        PromptMark::CommandFinished(exit_code)
                     }
+                    _ => return unhandled!(),
+                };
+                self.handler.set_prompt_mark(mark);
+            }
 
-                    let r = parse_hex!();
-                    let val = next!();
-                    if val != Some('/') {
-                        println!("val={:?}", val);
+            // Desktop notification (iTerm2): "9;body"
+            //
+            // ConEmu uses the same number for other purposes with a numeric first argument (e.g.,
+            // "9;4;state;progress"), which we do not support.
+            b"9" => {
+                if params.len() < 2 || (params.len() > 2 && parse_number(params[1]).is_some()) {
+                    return unhandled!();
+                }
+
+                // The body itself may contain the separator.
+                let body = params[1..].join(&b';');
+                self.handler.notify(None, &String::from_utf8_lossy(&body));
+            }
+
+            // Desktop notification (urxvt): "777;notify;title;body"
+            b"777" => {
+                if params.len() < 3 || params[1] != b"notify" {
+                    return unhandled!();
+                }
+
+                let title = String::from_utf8_lossy(params[2]);
+                let body = params[3..].join(&b';');
+                self.handler
+                    .notify(Some(&title), &String::from_utf8_lossy(&body));
+            }
+
+            // Set or query color index
+            b"4" => {
+                if params.len() < 3 || params.len() % 2 != 1 {
+                    return unhandled!();
+                }
+
+                for pair in params[1..].chunks(2) {
+                    let index = match parse_number(pair[0]) {
+                        Some(index) if index < 256 => index,
+                        _ => return unhandled!(),
+                    };
+
+                    if pair[1] == b"?" {
+                        self.handler
+                            .report_color(self.writer, index, self.state.osc_terminator);
+                    } else if let Some(color) = parse_rgb(pair[1]) {
+                        self.handler.set_color(index, color);
+                    } else {
                         return unhandled!();
                     }
-                    let g = parse_hex!();
-                    if next!() != Some('/') {
+                }
+            }
+
+            // Set or query the default foreground, background and cursor color
+            //
+            // Additional parameters refer to the following color, i.e., "10;fg;bg" sets both
+            // foreground and background.
+            b"10" | b"11" | b"12" => {
+                if params.len() < 2 {
+                    return unhandled!();
+                }
+
+                let dynamic_colors = [
+                    NamedColor::Foreground,
+                    NamedColor::Background,
+                    NamedColor::Cursor,
+                ];
+                let first = parse_number(params[0]).expect("valid number") - 10;
+                for (param, color) in params[1..].iter().zip(dynamic_colors[first..].iter()) {
+                    let index = *color as usize;
+                    if *param == b"?" {
+                        self.handler
+                            .report_color(self.writer, index, self.state.osc_terminator);
+                    } else if let Some(rgb) = parse_rgb(param) {
+                        self.handler.set_color(index, rgb);
+                    } else {
                         return unhandled!();
                     }
-                    let b = parse_hex!();
-
-                    Rgb { r, g, b }
-                };
+                }
+            }
 
-                self.handler.set_color(index as usize, color);
+            // Reset the default foreground, background and cursor color
+            b"110" => self
+                .handler
+                .reset_color(Some(NamedColor::Foreground as usize)),
+            b"111" => self
+                .handler
+                .reset_color(Some(NamedColor::Background as usize)),
+            b"112" => self.handler.reset_color(Some(NamedColor::Cursor as usize)),
+
+            // Reset color index
+            b"104" => {
+                // No (or an empty) argument means that the complete table is reset.
+                if params.len() == 1 || params[1].is_empty() {
+                    self.handler.reset_color(None);
+                    return;
+                }
+                for param in &params[1..] {
+                    match parse_number(param) {
+                        Some(index) if index < 256 => self.handler.reset_color(Some(index)),
+                        _ => return unhandled!(),
+                    }
+                }
             }
             _ => {
                 unhandled!();
@@ -786,6 +1378,7 @@ where
         let private = intermediates.get(0).map(|b| *b == b'?').unwrap_or(false);
         let handler = &mut self.handler;
         let writer = &mut self.writer;
+        let state = &*self.state;
 
         macro_rules! unhandled {
             () => {{
@@ -863,6 +1456,9 @@ where
                     None => unhandled!(),
                 }
             }
+            'p' if intermediates == b"$" || intermediates == b"?$" => {
+                handler.report_mode(writer, private, arg_or_default!(idx: 0, default: 0))
+            }
             'M' => handler.delete_lines(Line(arg_or_default!(idx: 0, default: 1) as usize)),
             'X' => handler.erase_chars(Column(arg_or_default!(idx: 0, default: 1) as usize)),
             'P' => handler.delete_chars(Column(arg_or_default!(idx: 0, default: 1) as usize)),
@@ -875,6 +1471,15 @@ where
                     None => unhandled!(),
                 }
             }
+            'm' if intermediates == b">" => {
+                let level = match args {
+                    [4] | [4, 0] => ModifyOtherKeys::Reset,
+                    [4, 1] => ModifyOtherKeys::EnableExceptWellDefined,
+                    [4, 2] => ModifyOtherKeys::EnableAll,
+                    _ => unhandled!(),
+                };
+                handler.set_modify_other_keys(level);
+            }
             'm' => {
                 // Sometimes a C-style for loop is just what you need
                 let mut i = 0; // C-for initializer
@@ -889,6 +1494,20 @@ where
                         break;
                     }
 
+                    // Attributes with colon separated sub-parameters are self-contained.
+                    let num_subparams = (i + 1..args.len())
+                        .take_while(|&j| state.is_subparam(j))
+                        .count();
+                    if num_subparams > 0 {
+                        let group = &args[i..=i + num_subparams];
+                        match parse_attr_with_subparams(group) {
+                            Some(attr) => handler.terminal_attribute(attr),
+                            None => warn!("[Unhandled SGR] {:?}", group),
+                        }
+                        i += num_subparams + 1;
+                        continue;
+                    }
+
                     let attr = match args[i] {
                         0 => Attr::Reset,
                         1 => Attr::Bold,
@@ -944,6 +1563,16 @@ where
                             }
                         }
                         49 => Attr::Background(Color::Named(NamedColor::Background)),
+                        58 => {
+                            let mut start = 0;
+                            if let Some(color) = parse_color(&args[i..], &mut start) {
+                                i += start;
+                                Attr::UnderlineColor(Some(color))
+                            } else {
+                                break;
+                            }
+                        }
+                        59 => Attr::UnderlineColor(None),
                         90 => Attr::Foreground(Color::Named(NamedColor::BrightBlack)),
                         91 => Attr::Foreground(Color::Named(NamedColor::BrightRed)),
                         92 => Attr::Foreground(Color::Named(NamedColor::BrightGreen)),
@@ -968,6 +1597,10 @@ where
                     i += 1; // C-for expr
                 }
             }
+            'n' if intermediates == b">" => match args {
+                [4] | [4, ..] => handler.set_modify_other_keys(ModifyOtherKeys::Reset),
+                _ => unhandled!(),
+            },
             'n' => handler.device_status(writer, arg_or_default!(idx: 0, default: 0) as usize),
             'r' => {
                 if private {
@@ -985,7 +1618,46 @@ where
                 handler.set_scrolling_region(top..bottom);
             }
             's' => handler.save_cursor_position(),
-            'u' => handler.restore_cursor_position(),
+            't' => {
+                if !intermediates.is_empty() {
+                    unhandled!();
+                }
+                match arg_or_default!(idx: 0, default: 0) {
+                    14 => handler.report_window_size(writer, WindowSizeReport::TextAreaPixels),
+                    16 => handler.report_window_size(writer, WindowSizeReport::CellPixels),
+                    18 => handler.report_window_size(writer, WindowSizeReport::TextAreaCells),
+                    op @ 22 | op @ 23 => {
+                        let kind = match args.get(1).cloned().unwrap_or(0) {
+                            0 => TitleKind::Both,
+                            1 => TitleKind::IconName,
+                            2 => TitleKind::Title,
+                            _ => unhandled!(),
+                        };
+                        if op == 22 {
+                            handler.push_title(kind);
+                        } else {
+                            handler.pop_title(kind);
+                        }
+                    }
+                    _ => unhandled!(),
+                }
+            }
+            'u' => match intermediates {
+                b">" => handler.push_keyboard_mode(arg_or_default!(idx: 0, default: 0) as u8),
+                b"<" => handler.pop_keyboard_modes(arg_or_default!(idx: 0, default: 1) as usize),
+                b"=" => {
+                    let behavior = match arg_or_default!(idx: 1, default: 1) {
+                        1 => KeyboardModesApplyBehavior::Replace,
+                        2 => KeyboardModesApplyBehavior::Union,
+                        3 => KeyboardModesApplyBehavior::Difference,
+                        _ => unhandled!(),
+                    };
+                    handler.set_keyboard_mode(arg_or_default!(idx: 0, default: 0) as u8, behavior);
+                }
+                b"?" => handler.report_keyboard_mode(writer),
+                b"" => handler.restore_cursor_position(),
+                _ => unhandled!(),
+            },
             'q' => {
                 let style = match arg_or_default!(idx: 0, default: 0) {
                     0..=2 => CursorStyle::Block,
@@ -1108,6 +1780,147 @@ fn parse_color(attrs: &[i64], i: &mut usize) -> Option<Color> {
     }
 }
 
+/// Parse an attribute from a parameter and its colon separated sub-parameters (e.g., `4:3` or
+/// `38:2::255:0:0`)
+fn parse_attr_with_subparams(group: &[i64]) -> Option<Attr> {
+    match group[0] {
+        4 => match group[1..] {
+            [0] => Some(Attr::CancelUnderline),
+            [1] => Some(Attr::Underscore),
+            [2] => Some(Attr::DoubleUnderline),
+            [3] => Some(Attr::Undercurl),
+            [4] => Some(Attr::DottedUnderline),
+            [5] => Some(Attr::DashedUnderline),
+            _ => None,
+        },
+        38 => parse_subparam_color(&group[1..]).map(Attr::Foreground),
+        48 => parse_subparam_color(&group[1..]).map(Attr::Background),
+        58 => parse_subparam_color(&group[1..]).map(|c| Attr::UnderlineColor(Some(c))),
+        _ => None,
+    }
+}
+
+/// Parse a color specifier from colon separated sub-parameters, i.e., `5:index` or
+/// `2:[colorspace]:r:g:b`
+fn parse_subparam_color(subparams: &[i64]) -> Option<Color> {
+    let component = |c: i64| {
+        if (0..256).contains(&c) {
+            Some(c as u8)
+        } else {
+            None
+        }
+    };
+    match *subparams {
+        [5, index] => component(index).map(Color::Indexed),
+        [2, r, g, b] | [2, _, r, g, b] => Some(Color::Spec(Rgb {
+            r: component(r)?,
+            g: component(g)?,
+            b: component(b)?,
+        })),
+        _ => None,
+    }
+}
+
+/// Parse a decimal number from an OSC parameter
+fn parse_number(input: &[u8]) -> Option<usize> {
+    if input.is_empty() {
+        return None;
+    }
+    let mut num: usize = 0;
+    for c in input {
+        let digit = (*c as char).to_digit(10)?;
+        num = num.checked_mul(10)?.checked_add(digit as usize)?;
+    }
+    Some(num)
+}
+
+/// Parse a URL of the form "file://host/path" into the host and (percent-decoded) path.
+///
+/// (Within OSC parameters, ';' is a separator, so it may only appear in the path encoded.)
+fn parse_file_url(input: &[u8]) -> Option<(String, PathBuf)> {
+    use std::os::unix::ffi::OsStringExt;
+
+    let rest = input.strip_prefix(b"file://")?;
+    let path_start = rest.iter().position(|b| *b == b'/')?;
+    let host = str::from_utf8(&rest[..path_start]).ok()?.to_owned();
+
+    let mut path = Vec::new();
+    let mut bytes = rest[path_start..].iter();
+    while let Some(byte) = bytes.next() {
+        if *byte == b'%' {
+            let hex = [*bytes.next()?, *bytes.next()?];
+            path.push(u8::from_str_radix(str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            path.push(*byte);
+        }
+    }
+    Some((host, PathBuf::from(OsString::from_vec(path))))
+}
+
+/// Decode a string of pairs of hex digits (as used by XTGETTCAP), or return `None` if the input is
+/// not valid.
+fn decode_hex(input: &[u8]) -> Option<Vec<u8>> {
+    if input.len() % 2 != 0 {
+        return None;
+    }
+    let digit = |b: u8| (b as char).to_digit(16);
+    input
+        .chunks(2)
+        .map(|pair| Some((digit(pair[0])? << 4 | digit(pair[1])?) as u8))
+        .collect()
+}
+
+/// Encode the data as pairs of (upper case) hex digits (as used by XTGETTCAP).
+pub fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Parse a color specifier in one of the forms "rgb:r/g/b" (with 1 to 4 hex digits per component)
+/// or "#rrggbb".
+fn parse_rgb(input: &[u8]) -> Option<Rgb> {
+    let input = str::from_utf8(input).ok()?;
+
+    // Scale a component with an arbitrary number of hex digits to 8 bit.
+    fn component(digits: &str) -> Option<u8> {
+        if digits.is_empty() || digits.len() > 4 {
+            return None;
+        }
+        let value = u32::from_str_radix(digits, 16).ok()?;
+        let max = (1u32 << (4 * digits.len())) - 1;
+        Some((value * 255 / max) as u8)
+    }
+
+    if let Some(spec) = input.strip_prefix("rgb:") {
+        let mut components = spec.split('/');
+        let r = component(components.next()?)?;
+        let g = component(components.next()?)?;
+        let b = component(components.next()?)?;
+        if components.next().is_some() {
+            return None;
+        }
+        Some(Rgb { r, g, b })
+    } else if let Some(spec) = input.strip_prefix('#') {
+        if spec.len() != 6 || !spec.is_ascii() {
+            return None;
+        }
+        Some(Rgb {
+            r: component(&spec[0..2])?,
+            g: component(&spec[2..4])?,
+            b: component(&spec[4..6])?,
+        })
+    } else {
+        None
+    }
+}
+
+/// Format a color in the form that xterm uses to answer color queries, i.e., "rgb:rrrr/gggg/bbbb"
+pub fn format_rgb(color: Rgb) -> String {
+    format!(
+        "rgb:{0:02x}{0:02x}/{1:02x}{1:02x}/{2:02x}{2:02x}",
+        color.r, color.g, color.b
+    )
+}
+
 /// C0 set of 7-bit control characters (from ANSI X3.4-1977).
 #[allow(non_snake_case)]
 pub mod C0 {
@@ -1257,7 +2070,11 @@ pub mod C1 {
 // Byte sequences used in these tests are recording of pty stdout.
 #[cfg(test)]
 mod tests {
-    use super::{Attr, CharsetIndex, Color, Handler, Processor, Rgb, StandardCharset, TermInfo};
+    use super::{
+        decode_hex, encode_hex, parse_file_url, parse_rgb, Attr, CharsetIndex, Color, Handler,
+        Hyperlink, KeyboardModesApplyBehavior, ModifyOtherKeys, OscTerminator, Processor, Rgb,
+        StandardCharset, TermInfo,
+    };
     use index::{Column, Line};
     use std::io;
 
@@ -1332,6 +2149,111 @@ mod tests {
         assert_eq!(handler.attr, Some(Attr::Foreground(Color::Spec(spec))));
     }
 
+    #[derive(Default)]
+    struct KeyboardHandler {
+        modes: Vec<u8>,
+        modify_other_keys: Option<ModifyOtherKeys>,
+        restored_cursor: bool,
+    }
+
+    impl Handler for KeyboardHandler {
+        fn push_keyboard_mode(&mut self, flags: u8) {
+            self.modes.push(flags);
+        }
+
+        fn pop_keyboard_modes(&mut self, count: usize) {
+            for _ in 0..count {
+                self.modes.pop();
+            }
+        }
+
+        fn set_keyboard_mode(&mut self, flags: u8, behavior: KeyboardModesApplyBehavior) {
+            let top = self.modes.last_mut().unwrap();
+            match behavior {
+                KeyboardModesApplyBehavior::Replace => *top = flags,
+                KeyboardModesApplyBehavior::Union => *top |= flags,
+                KeyboardModesApplyBehavior::Difference => *top &= !flags,
+            }
+        }
+
+        fn report_keyboard_mode<W: io::Write>(&mut self, writer: &mut W) {
+            write!(writer, "{:?}", self.modes.last()).unwrap();
+        }
+
+        fn set_modify_other_keys(&mut self, level: ModifyOtherKeys) {
+            self.modify_other_keys = Some(level);
+        }
+
+        fn restore_cursor_position(&mut self) {
+            self.restored_cursor = true;
+        }
+    }
+
+    impl TermInfo for KeyboardHandler {
+        fn lines(&self) -> Line {
+            Line(24)
+        }
+
+        fn cols(&self) -> Column {
+            Column(80)
+        }
+    }
+
+    #[test]
+    fn parse_keyboard_modes() {
+        let mut parser = Processor::new();
+        let mut handler = KeyboardHandler::default();
+        let mut reply = Vec::new();
+        for byte in b"\x1b[>1u\x1b[>8u\x1b[=3;2u\x1b[?u\x1b[=1;3u\x1b[<u\x1b[?u" {
+            parser.advance(&mut handler, *byte, &mut reply);
+        }
+        assert_eq!(handler.modes, vec![1]);
+        assert_eq!(reply, b"Some(11)Some(1)".to_vec());
+        assert!(!handler.restored_cursor);
+
+        for byte in b"\x1b[>4;2m\x1b[u" {
+            parser.advance(&mut handler, *byte, &mut reply);
+        }
+        assert_eq!(handler.modify_other_keys, Some(ModifyOtherKeys::EnableAll));
+        assert!(handler.restored_cursor);
+
+        for byte in b"\x1b[>4m" {
+            parser.advance(&mut handler, *byte, &mut reply);
+        }
+        assert_eq!(handler.modify_other_keys, Some(ModifyOtherKeys::Reset));
+    }
+
+    #[test]
+    fn parse_attr_subparams() {
+        let parse = |bytes: &[u8]| {
+            let mut parser = Processor::new();
+            let mut handler = AttrHandler::default();
+            for byte in bytes {
+                parser.advance(&mut handler, *byte, &mut Void);
+            }
+            handler.attr
+        };
+        let spec = Color::Spec(Rgb { r: 1, g: 2, b: 3 });
+
+        assert_eq!(parse(b"\x1b[4:3m"), Some(Attr::Undercurl));
+        assert_eq!(parse(b"\x1b[1;4:5m"), Some(Attr::DashedUnderline));
+        assert_eq!(parse(b"\x1b[4:2;1m"), Some(Attr::Bold));
+        assert_eq!(parse(b"\x1b[4:0m"), Some(Attr::CancelUnderline));
+        assert_eq!(parse(b"\x1b[38:2::1:2:3m"), Some(Attr::Foreground(spec)));
+        assert_eq!(parse(b"\x1b[48:2:1:2:3m"), Some(Attr::Background(spec)));
+        assert_eq!(
+            parse(b"\x1b[58:5:9m"),
+            Some(Attr::UnderlineColor(Some(Color::Indexed(9))))
+        );
+        assert_eq!(
+            parse(b"\x1b[58;2;1;2;3m"),
+            Some(Attr::UnderlineColor(Some(spec)))
+        );
+        assert_eq!(parse(b"\x1b[59m"), Some(Attr::UnderlineColor(None)));
+        // Colons are only special within CSI parameters.
+        assert_eq!(parse(b"a:b\x1b[4m"), Some(Attr::Underscore));
+    }
+
     /// No exactly a test; useful for debugging
     #[test]
     fn parse_zsh_startup() {
@@ -1431,4 +2353,202 @@ mod tests {
 
         assert_eq!(handler.index, CharsetIndex::G1);
     }
+
+    #[derive(Default)]
+    struct ColorHandler {
+        colors: Vec<(usize, Rgb)>,
+        reset: Vec<Option<usize>>,
+    }
+
+    impl Handler for ColorHandler {
+        fn set_color(&mut self, index: usize, color: Rgb) {
+            self.colors.push((index, color));
+        }
+
+        fn reset_color(&mut self, index: Option<usize>) {
+            self.reset.push(index);
+        }
+
+        fn report_color<W: io::Write>(
+            &mut self,
+            writer: &mut W,
+            index: usize,
+            terminator: OscTerminator,
+        ) {
+            write!(writer, "{}{}", index, terminator.as_str()).unwrap();
+        }
+    }
+
+    impl TermInfo for ColorHandler {
+        fn lines(&self) -> Line {
+            Line(24)
+        }
+
+        fn cols(&self) -> Column {
+            Column(80)
+        }
+    }
+
+    #[test]
+    fn parse_osc_color_index() {
+        static BYTES: &'static [u8] =
+            b"\x1b]4;1;rgb:ff/80/00;255;?;17;#102030\x07\x1b]104;3\x07\x1b]104\x07";
+        let mut parser = Processor::new();
+        let mut handler = ColorHandler::default();
+        let mut reply = Vec::new();
+
+        for byte in BYTES {
+            parser.advance(&mut handler, *byte, &mut reply);
+        }
+
+        assert_eq!(
+            handler.colors,
+            vec![
+                (
+                    1,
+                    Rgb {
+                        r: 0xff,
+                        g: 0x80,
+                        b: 0x00
+                    }
+                ),
+                (
+                    17,
+                    Rgb {
+                        r: 0x10,
+                        g: 0x20,
+                        b: 0x30
+                    }
+                ),
+            ]
+        );
+        assert_eq!(reply, b"255\x07");
+        assert_eq!(handler.reset, vec![Some(3), None]);
+    }
+
+    #[test]
+    fn parse_osc_dynamic_colors() {
+        static BYTES: &[u8] = b"\x1b]10;#102030;?\x07\x1b]12;?\x1b\\\x1b]111\x07";
+        let mut parser = Processor::new();
+        let mut handler = ColorHandler::default();
+        let mut reply = Vec::new();
+
+        for byte in BYTES {
+            parser.advance(&mut handler, *byte, &mut reply);
+        }
+
+        assert_eq!(
+            handler.colors,
+            vec![(
+                256,
+                Rgb {
+                    r: 0x10,
+                    g: 0x20,
+                    b: 0x30
+                }
+            )]
+        );
+        assert_eq!(reply, b"257\x07259\x1b\\");
+        assert_eq!(handler.reset, vec![Some(257)]);
+    }
+
+    #[derive(Default)]
+    struct HyperlinkHandler {
+        links: Vec<Option<Hyperlink>>,
+    }
+
+    impl Handler for HyperlinkHandler {
+        fn set_hyperlink(&mut self, link: Option<Hyperlink>) {
+            self.links.push(link);
+        }
+    }
+
+    impl TermInfo for HyperlinkHandler {
+        fn lines(&self) -> Line {
+            Line(24)
+        }
+
+        fn cols(&self) -> Column {
+            Column(80)
+        }
+    }
+
+    #[test]
+    fn parse_hyperlinks() {
+        let mut parser = Processor::new();
+        let mut handler = HyperlinkHandler::default();
+        let input = b"\x1b]8;;http://a.b/c;d\x1b\\x\x1b]8;;\x1b\\\x1b]8;foo=1:id=xy;file:///e\x07";
+        for byte in &input[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+        assert_eq!(
+            handler.links,
+            vec![
+                Some(Hyperlink {
+                    id: None,
+                    uri: "http://a.b/c;d".to_owned()
+                }),
+                None,
+                Some(Hyperlink {
+                    id: Some("xy".to_owned()),
+                    uri: "file:///e".to_owned()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn hex_strings() {
+        assert_eq!(decode_hex(b"544e"), Some(b"TN".to_vec()));
+        assert_eq!(decode_hex(b""), Some(Vec::new()));
+        assert_eq!(decode_hex(b"544"), None);
+        assert_eq!(decode_hex(b"+1"), None);
+        assert_eq!(encode_hex(b"TN\x1b"), "544E1B");
+    }
+
+    #[test]
+    fn parse_file_urls() {
+        assert_eq!(
+            parse_file_url(b"file://host/home/user/My%20Files"),
+            Some(("host".to_owned(), "/home/user/My Files".into()))
+        );
+        assert_eq!(
+            parse_file_url(b"file:///tmp"),
+            Some(("".to_owned(), "/tmp".into()))
+        );
+        assert_eq!(parse_file_url(b"file://host"), None);
+        assert_eq!(parse_file_url(b"file://host/%2"), None);
+        assert_eq!(parse_file_url(b"http://host/tmp"), None);
+    }
+
+    #[test]
+    fn parse_rgb_spec() {
+        assert_eq!(
+            parse_rgb(b"rgb:f/8/0"),
+            Some(Rgb {
+                r: 0xff,
+                g: 0x88,
+                b: 0x00
+            })
+        );
+        assert_eq!(
+            parse_rgb(b"rgb:ffff/8080/0000"),
+            Some(Rgb {
+                r: 0xff,
+                g: 0x80,
+                b: 0x00
+            })
+        );
+        assert_eq!(
+            parse_rgb(b"#ff8000"),
+            Some(Rgb {
+                r: 0xff,
+                g: 0x80,
+                b: 0x00
+            })
+        );
+        assert_eq!(parse_rgb(b"rgb:ff/80"), None);
+        assert_eq!(parse_rgb(b"rgb:ff/80/00/00"), None);
+        assert_eq!(parse_rgb(b"red"), None);
+    }
 }
@@ -0,0 +1,103 @@
+//! Access of the program running in the terminal to the clipboard of the application (OSC 52).
+
+/// Determines which kind of access to the clipboard (via OSC 52) is granted to the program running
+/// in the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClipboardPolicy {
+    /// Whether the program may set the clipboard (see `TerminalEvent::ClipboardSet`)
+    pub allow_write: bool,
+    /// Whether the program may query the clipboard (see `TerminalEvent::ClipboardRequest`)
+    pub allow_read: bool,
+    /// Maximum size of (decoded) clipboard contents set by the program in bytes. Larger contents
+    /// are ignored. (Independently of this, OSC strings longer than 4 MiB are always dropped.)
+    pub max_size: usize,
+}
+
+/// Setting the clipboard (up to 1 MiB) is allowed, but reading it is not.
+impl Default for ClipboardPolicy {
+    fn default() -> Self {
+        ClipboardPolicy {
+            allow_write: true,
+            allow_read: false,
+            max_size: 1 << 20,
+        }
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode the data as (padded) base64.
+pub fn encode_base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decode (optionally padded) base64, or return `None` if the input is not valid base64.
+pub fn decode_base64(encoded: &[u8]) -> Option<Vec<u8>> {
+    let end = encoded
+        .iter()
+        .rposition(|b| *b != b'=')
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    if encoded.len() - end > 2 || encoded[..end].len() % 4 == 1 {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(end / 4 * 3 + 2);
+    let mut group = 0u32;
+    for (i, b) in encoded[..end].iter().enumerate() {
+        let value = BASE64_ALPHABET.iter().position(|a| a == b)? as u32;
+        group = group << 6 | value;
+        if i % 4 == 3 {
+            decoded.extend_from_slice(&[(group >> 16) as u8, (group >> 8) as u8, group as u8]);
+            group = 0;
+        }
+    }
+    match end % 4 {
+        2 => decoded.push((group >> 4) as u8),
+        3 => decoded.extend_from_slice(&[(group >> 10) as u8, (group >> 2) as u8]),
+        _ => {}
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xff\xfe\x00", "//4A"),
+        ];
+        for (data, encoded) in cases {
+            assert_eq!(encode_base64(data), *encoded);
+            assert_eq!(decode_base64(encoded.as_bytes()).as_deref(), Some(*data));
+        }
+        assert_eq!(decode_base64(b"Zm8"), Some(b"fo".to_vec()));
+        assert_eq!(decode_base64(b"Z"), None);
+        assert_eq!(decode_base64(b"Zm9v!"), None);
+        assert_eq!(decode_base64(b"Zg==="), None);
+    }
+}
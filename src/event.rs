@@ -0,0 +1,31 @@
+//! Notifications from the program running in the terminal to the application.
+use ansi::{ClipboardSelection, WorkingDirectory};
+
+/// Something that happened in the terminal that the application may want to react to.
+///
+/// Events are collected while processing input in `Terminal::add_byte_input` and can be retrieved
+/// using `Terminal::drain_events`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TerminalEvent {
+    /// The window title and/or icon name have been changed.
+    TitleChanged { title: String, icon_name: String },
+    /// The bell has been rung.
+    Bell,
+    /// The program wants to set the contents of the given selections (if allowed by the
+    /// `ClipboardPolicy`).
+    ClipboardSet {
+        selections: Vec<ClipboardSelection>,
+        contents: String,
+    },
+    /// The program wants to know the contents of the given selection (if allowed by the
+    /// `ClipboardPolicy`). Answer using `Terminal::answer_clipboard_request`.
+    ClipboardRequest { selection: ClipboardSelection },
+    /// The program wants to show a desktop notification (using OSC 9 or OSC 777).
+    Notification { title: Option<String>, body: String },
+    /// The program (usually a shell) has reported a new working directory.
+    WorkingDirectoryChanged(WorkingDirectory),
+    /// The pty has been found to be closed (see `Terminal::is_closed`), usually because the
+    /// program running in the terminal has exited.
+    Closed,
+}
@@ -0,0 +1,756 @@
+//! Encoding of user input for the program running in the terminal.
+use ansi::ModifyOtherKeys;
+use unsegen::input::{Event, Input, Key, MouseButton, MouseEvent};
+
+/// Flags of the kitty keyboard protocol (reporting alternate keys (4) and associated text (16) is
+/// not supported)
+pub const KITTY_DISAMBIGUATE: u8 = 1;
+pub const KITTY_REPORT_EVENT_TYPES: u8 = 2;
+pub const KITTY_REPORT_ALL_KEYS: u8 = 8;
+
+/// Flags of the kitty keyboard protocol that we support. termion only reports key presses (which
+/// are not annotated), so reporting event types is trivially supported.
+pub const KITTY_SUPPORTED_FLAGS: u8 =
+    KITTY_DISAMBIGUATE | KITTY_REPORT_EVENT_TYPES | KITTY_REPORT_ALL_KEYS;
+
+/// Mouse events that the program running in the terminal wants to be reported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MouseProtocol {
+    /// ?9: Button presses only
+    X10,
+    /// ?1000: Button presses and releases
+    Clicks,
+    /// ?1002: Additionally, motion while a button is pressed
+    ButtonMotion,
+    /// ?1003: Additionally, all motion
+    AnyMotion,
+}
+
+/// How reported mouse events are encoded.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MouseEncoding {
+    /// `CSI M Cb Cx Cy` with single byte values (i.e., limited to 223 rows/columns)
+    #[default]
+    Default,
+    /// ?1006: `CSI < Cb ; Cx ; Cy M` (or `m` for releases)
+    Sgr,
+    /// ?1015: `CSI Cb ; Cx ; Cy M`
+    Urxvt,
+}
+
+/// Input related modes that can be set by the program running in the terminal.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct InputModes {
+    pub mouse_protocol: Option<MouseProtocol>,
+    pub mouse_encoding: MouseEncoding,
+    /// DECCKM: Cursor keys send SS3 instead of CSI sequences
+    pub application_cursor_keys: bool,
+    /// DECKPAM: Keypad keys send SS3 sequences instead of the characters printed on them
+    pub application_keypad: bool,
+    /// ?2004: Pasted text is enclosed in `CSI 200 ~` and `CSI 201 ~`
+    pub bracketed_paste: bool,
+    /// ?1004: Gaining and losing focus is reported with `CSI I` and `CSI O`
+    pub focus_events: bool,
+    /// Current flags of the kitty keyboard protocol
+    pub keyboard_flags: u8,
+    /// xterm's modifyOtherKeys
+    pub modify_other_keys: ModifyOtherKeys,
+}
+
+/// Modifier keys that are held while a key is pressed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl Modifiers {
+    /// No modifiers at all
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    fn any(&self) -> bool {
+        self.shift || self.alt || self.ctrl
+    }
+
+    /// The modifier parameter of xterm-style key sequences (e.g., `CSI 1 ; 5 A` for Ctrl+Up)
+    fn parameter(&self) -> u8 {
+        1 + self.shift as u8 + 2 * self.alt as u8 + 4 * self.ctrl as u8
+    }
+}
+
+/// Keys of the numeric keypad, which termion does not distinguish from the rest of the keyboard.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeypadKey {
+    Digit(u8),
+    Enter,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Decimal,
+    Equal,
+}
+
+impl KeypadKey {
+    /// Character that is sent in numeric keypad mode
+    fn numeric(self) -> char {
+        match self {
+            KeypadKey::Digit(d) => (b'0' + d % 10) as char,
+            KeypadKey::Enter => '\r',
+            KeypadKey::Plus => '+',
+            KeypadKey::Minus => '-',
+            KeypadKey::Multiply => '*',
+            KeypadKey::Divide => '/',
+            KeypadKey::Decimal => '.',
+            KeypadKey::Equal => '=',
+        }
+    }
+
+    /// Final character of the SS3 sequence that is sent in application keypad mode
+    fn application(self) -> u8 {
+        match self {
+            KeypadKey::Digit(d) => b'p' + d % 10,
+            KeypadKey::Enter => b'M',
+            KeypadKey::Plus => b'k',
+            KeypadKey::Minus => b'm',
+            KeypadKey::Multiply => b'j',
+            KeypadKey::Divide => b'o',
+            KeypadKey::Decimal => b'n',
+            KeypadKey::Equal => b'X',
+        }
+    }
+
+    fn from_application(byte: u8) -> Option<Self> {
+        Some(match byte {
+            b'p'..=b'y' => KeypadKey::Digit(byte - b'p'),
+            b'M' => KeypadKey::Enter,
+            b'k' => KeypadKey::Plus,
+            b'm' => KeypadKey::Minus,
+            b'j' => KeypadKey::Multiply,
+            b'o' => KeypadKey::Divide,
+            b'n' => KeypadKey::Decimal,
+            b'X' => KeypadKey::Equal,
+            _ => return None,
+        })
+    }
+}
+
+/// Translates input events into the byte sequences that are sent to the program running in the
+/// terminal, according to the modes it has requested.
+#[derive(Default)]
+pub struct InputEncoder {
+    pub modes: InputModes,
+
+    // termion does not tell us which button was released or is held during motion, so we have to
+    // remember the last one that was pressed.
+    pressed_button: Option<MouseButton>,
+}
+
+impl InputEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Translate raw input (as received from the host terminal) into what the program running in
+    /// the terminal expects.
+    ///
+    /// Only keys whose encoding depends on the modes of the terminal are translated, everything
+    /// else is passed on as is.
+    pub fn encode_input(&self, input: &Input) -> Vec<u8> {
+        let translated = match input.event {
+            Event::Key(key @ Key::Up)
+            | Event::Key(key @ Key::Down)
+            | Event::Key(key @ Key::Right)
+            | Event::Key(key @ Key::Left)
+            | Event::Key(key @ Key::Home)
+            | Event::Key(key @ Key::End) => self.encode_key(key, Modifiers::none()),
+            Event::Key(key) => self.encode_extended_key(key, Modifiers::none()),
+            // The host terminal may itself be in application cursor key or keypad mode, in which
+            // case termion does not understand what it sends.
+            _ => match *input.raw.as_slice() {
+                [0x1b, b'O', byte] => match cursor_key(byte) {
+                    Some(key) => self.encode_key(key, Modifiers::none()),
+                    None => KeypadKey::from_application(byte)
+                        .map(|key| self.encode_keypad_key(key, Modifiers::none())),
+                },
+                _ => None,
+            },
+        };
+        translated.unwrap_or_else(|| input.raw.clone())
+    }
+
+    /// Encode a key press.
+    ///
+    /// `None` is returned for keys that cannot be encoded.
+    pub fn encode_key(&self, key: Key, modifiers: Modifiers) -> Option<Vec<u8>> {
+        if let Some(bytes) = self.encode_extended_key(key, modifiers) {
+            return Some(bytes);
+        }
+        let csi_or_ss3 = |final_byte: u8| {
+            if modifiers.any() {
+                format!("\x1b[1;{}{}", modifiers.parameter(), final_byte as char).into_bytes()
+            } else {
+                vec![0x1b, b'O', final_byte]
+            }
+        };
+        let cursor = |final_byte: u8| {
+            if !modifiers.any() && !self.modes.application_cursor_keys {
+                vec![0x1b, b'[', final_byte]
+            } else {
+                csi_or_ss3(final_byte)
+            }
+        };
+        let tilde = |number: u8| {
+            if modifiers.any() {
+                format!("\x1b[{};{}~", number, modifiers.parameter()).into_bytes()
+            } else {
+                format!("\x1b[{}~", number).into_bytes()
+            }
+        };
+        let with_alt = |mut bytes: Vec<u8>| {
+            if modifiers.alt {
+                bytes.insert(0, 0x1b);
+            }
+            bytes
+        };
+        Some(match key {
+            Key::Up => cursor(b'A'),
+            Key::Down => cursor(b'B'),
+            Key::Right => cursor(b'C'),
+            Key::Left => cursor(b'D'),
+            Key::End => cursor(b'F'),
+            Key::Home => cursor(b'H'),
+            Key::Insert => tilde(2),
+            Key::Delete => tilde(3),
+            Key::PageUp => tilde(5),
+            Key::PageDown => tilde(6),
+            Key::F(n @ 1..=4) => csi_or_ss3(b'P' + n - 1),
+            Key::F(n @ 5..=12) => tilde([15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5]),
+            Key::F(_) => return None,
+            Key::BackTab => b"\x1b[Z".to_vec(),
+            Key::Backspace if modifiers.ctrl => with_alt(vec![0x08]),
+            Key::Backspace => with_alt(vec![0x7f]),
+            Key::Esc => with_alt(vec![0x1b]),
+            Key::Null => with_alt(vec![0]),
+            Key::Alt(c) => {
+                return self.encode_key(
+                    Key::Char(c),
+                    Modifiers {
+                        alt: true,
+                        ..modifiers
+                    },
+                )
+            }
+            Key::Ctrl(c) => {
+                return self.encode_key(
+                    Key::Char(c),
+                    Modifiers {
+                        ctrl: true,
+                        ..modifiers
+                    },
+                )
+            }
+            // termion reports carriage returns as newlines, but the enter key sends the former.
+            Key::Char('\n') => with_alt(vec![b'\r']),
+            Key::Char(c) if modifiers.ctrl => match c {
+                'a'..='z' => with_alt(vec![c as u8 - b'a' + 1]),
+                '@'..='_' => with_alt(vec![c as u8 - b'@']),
+                ' ' => with_alt(vec![0]),
+                '?' => with_alt(vec![0x7f]),
+                _ => with_alt(c.to_string().into_bytes()),
+            },
+            Key::Char(c) => with_alt(c.to_string().into_bytes()),
+            _ => return None,
+        })
+    }
+
+    /// Encode a key that produces a character (or control code) according to the kitty keyboard
+    /// protocol or modifyOtherKeys, if enabled.
+    ///
+    /// `None` is returned if the key should be encoded as usual.
+    fn encode_extended_key(&self, key: Key, modifiers: Modifiers) -> Option<Vec<u8>> {
+        let (c, modifiers) = match key {
+            // termion reports carriage returns as newlines, but the enter key sends the former.
+            Key::Char('\n') => ('\r', modifiers),
+            Key::Char(c) => (c, modifiers),
+            Key::Ctrl(c) => (
+                c,
+                Modifiers {
+                    ctrl: true,
+                    ..modifiers
+                },
+            ),
+            Key::Alt(c) => (
+                c,
+                Modifiers {
+                    alt: true,
+                    ..modifiers
+                },
+            ),
+            Key::Null => (
+                ' ',
+                Modifiers {
+                    ctrl: true,
+                    ..modifiers
+                },
+            ),
+            Key::Esc => ('\x1b', modifiers),
+            Key::Backspace => ('\x7f', modifiers),
+            _ => return None,
+        };
+
+        let flags = self.modes.keyboard_flags;
+        if flags & (KITTY_DISAMBIGUATE | KITTY_REPORT_ALL_KEYS) != 0 {
+            // Keys are identified by their unshifted character.
+            let (c, modifiers) = if c.is_ascii_uppercase() {
+                (
+                    c.to_ascii_lowercase(),
+                    Modifiers {
+                        shift: true,
+                        ..modifiers
+                    },
+                )
+            } else {
+                (c, modifiers)
+            };
+            let legacy = flags & KITTY_REPORT_ALL_KEYS == 0
+                && match c {
+                    '\x1b' => false,
+                    '\r' | '\t' | '\x7f' => !modifiers.any(),
+                    _ => !modifiers.alt && !modifiers.ctrl,
+                };
+            if legacy {
+                return None;
+            }
+            return Some(if modifiers.any() {
+                format!("\x1b[{};{}u", c as u32, modifiers.parameter()).into_bytes()
+            } else {
+                format!("\x1b[{}u", c as u32).into_bytes()
+            });
+        }
+
+        // Shift alone only changes the character that is produced.
+        let well_defined = !modifiers.ctrl && !modifiers.alt
+            || match self.modes.modify_other_keys {
+                ModifyOtherKeys::Reset => true,
+                ModifyOtherKeys::EnableExceptWellDefined => {
+                    !modifiers.ctrl || matches!(c, 'a'..='z' | '@'..='_' | ' ' | '?')
+                }
+                ModifyOtherKeys::EnableAll => false,
+            };
+        if well_defined {
+            None
+        } else {
+            Some(format!("\x1b[27;{};{}~", modifiers.parameter(), c as u32).into_bytes())
+        }
+    }
+
+    /// Encode a key press on the numeric keypad.
+    pub fn encode_keypad_key(&self, key: KeypadKey, modifiers: Modifiers) -> Vec<u8> {
+        if self.modes.application_keypad {
+            if modifiers.any() {
+                format!(
+                    "\x1bO{}{}",
+                    modifiers.parameter(),
+                    key.application() as char
+                )
+                .into_bytes()
+            } else {
+                vec![0x1b, b'O', key.application()]
+            }
+        } else {
+            self.encode_key(Key::Char(key.numeric()), modifiers)
+                .unwrap_or_default()
+        }
+    }
+
+    /// Encode pasted text.
+    pub fn encode_paste(&self, text: &str) -> Vec<u8> {
+        if self.modes.bracketed_paste {
+            // The program must not be fooled into thinking that the paste ends prematurely, so we
+            // remove all end markers (also those that only emerge after removing others).
+            let mut text = text.to_owned();
+            while text.contains(PASTE_END) {
+                text = text.replace(PASTE_END, "");
+            }
+            format!("{}{}{}", PASTE_START, text, PASTE_END).into_bytes()
+        } else {
+            // Without bracketed paste, pasting behaves like typing, where enter sends CR.
+            text.replace("\r\n", "\r").replace('\n', "\r").into_bytes()
+        }
+    }
+
+    /// Encode a change of focus.
+    ///
+    /// `None` is returned if focus changes should not be reported.
+    pub fn encode_focus(&self, focused: bool) -> Option<Vec<u8>> {
+        if !self.modes.focus_events {
+            return None;
+        }
+        Some(if focused { b"\x1b[I" } else { b"\x1b[O" }.to_vec())
+    }
+
+    /// Encode a mouse event (with one-based coordinates relative to the terminal window).
+    ///
+    /// `None` is returned if the event should not be reported.
+    pub fn encode_mouse(&mut self, event: MouseEvent) -> Option<Vec<u8>> {
+        let (button, x, y, action) = match event {
+            MouseEvent::Press(button, x, y) => {
+                if !is_wheel(button) {
+                    self.pressed_button = Some(button);
+                }
+                (Some(button), x, y, MouseAction::Press)
+            }
+            MouseEvent::Release(x, y) => (self.pressed_button.take(), x, y, MouseAction::Release),
+            MouseEvent::Hold(x, y) => (self.pressed_button, x, y, MouseAction::Motion),
+        };
+        if x == 0 || y == 0 {
+            return None;
+        }
+
+        let reported = match (self.modes.mouse_protocol?, action) {
+            (MouseProtocol::X10, MouseAction::Press) => matches!(button, Some(b) if !is_wheel(b)),
+            (MouseProtocol::X10, _) => false,
+            (_, MouseAction::Press) | (_, MouseAction::Release) => true,
+            (MouseProtocol::ButtonMotion, MouseAction::Motion) => button.is_some(),
+            (MouseProtocol::AnyMotion, MouseAction::Motion) => true,
+            (MouseProtocol::Clicks, MouseAction::Motion) => false,
+        };
+        if !reported {
+            return None;
+        }
+
+        let button_code = match button {
+            Some(MouseButton::Left) => 0,
+            Some(MouseButton::Middle) => 1,
+            Some(MouseButton::Right) => 2,
+            Some(MouseButton::WheelUp) => 64,
+            Some(MouseButton::WheelDown) => 65,
+            None => 3,
+        };
+        let motion_code = if action == MouseAction::Motion { 32 } else { 0 };
+
+        match self.modes.mouse_encoding {
+            MouseEncoding::Sgr => {
+                // SGR encoding reports which button was released.
+                let (code, final_char) = match action {
+                    MouseAction::Release if button.is_none() => (0, 'm'),
+                    MouseAction::Release => (button_code, 'm'),
+                    _ => (button_code + motion_code, 'M'),
+                };
+                Some(format!("\x1b[<{};{};{}{}", code, x, y, final_char).into_bytes())
+            }
+            MouseEncoding::Urxvt => {
+                let code = legacy_code(button_code, action) + motion_code;
+                Some(format!("\x1b[{};{};{}M", 32 + code, x, y).into_bytes())
+            }
+            MouseEncoding::Default => {
+                let code = legacy_code(button_code, action) + motion_code;
+                // Coordinates that do not fit into a byte cannot be reported.
+                let encode = |v: u16| if v <= 223 { Some(32 + v as u8) } else { None };
+                Some(vec![0x1b, b'[', b'M', 32 + code, encode(x)?, encode(y)?])
+            }
+        }
+    }
+}
+
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+/// The cursor key that sends the given final byte (in SS3 or CSI sequences)
+fn cursor_key(final_byte: u8) -> Option<Key> {
+    Some(match final_byte {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'F' => Key::End,
+        b'H' => Key::Home,
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MouseAction {
+    Press,
+    Release,
+    Motion,
+}
+
+fn is_wheel(button: MouseButton) -> bool {
+    matches!(button, MouseButton::WheelUp | MouseButton::WheelDown)
+}
+
+/// Legacy encodings do not report which button was released.
+fn legacy_code(button_code: u8, action: MouseAction) -> u8 {
+    if action == MouseAction::Release {
+        3
+    } else {
+        button_code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoder(protocol: MouseProtocol, encoding: MouseEncoding) -> InputEncoder {
+        let mut encoder = InputEncoder::new();
+        encoder.modes.mouse_protocol = Some(protocol);
+        encoder.modes.mouse_encoding = encoding;
+        encoder
+    }
+
+    #[test]
+    fn cursor_keys() {
+        let mut encoder = InputEncoder::new();
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::none()
+        };
+        assert_eq!(
+            encoder.encode_key(Key::Up, Modifiers::none()),
+            Some(b"\x1b[A".to_vec())
+        );
+        assert_eq!(
+            encoder.encode_key(Key::Home, ctrl),
+            Some(b"\x1b[1;5H".to_vec())
+        );
+        encoder.modes.application_cursor_keys = true;
+        assert_eq!(
+            encoder.encode_key(Key::Up, Modifiers::none()),
+            Some(b"\x1bOA".to_vec())
+        );
+        assert_eq!(
+            encoder.encode_key(Key::Home, ctrl),
+            Some(b"\x1b[1;5H".to_vec())
+        );
+
+        // Input from a host terminal in the "wrong" mode is translated.
+        let input = |raw: &[u8], event| Input {
+            event,
+            raw: raw.to_vec(),
+        };
+        assert_eq!(
+            encoder.encode_input(&input(b"\x1b[D", Event::Key(Key::Left))),
+            b"\x1bOD".to_vec()
+        );
+        encoder.modes.application_cursor_keys = false;
+        assert_eq!(
+            encoder.encode_input(&input(b"\x1bOD", Event::Unsupported(b"\x1bOD".to_vec()))),
+            b"\x1b[D".to_vec()
+        );
+        assert_eq!(
+            encoder.encode_input(&input(
+                b"\x1b[1;2D",
+                Event::Unsupported(b"\x1b[1;2D".to_vec())
+            )),
+            b"\x1b[1;2D".to_vec()
+        );
+    }
+
+    #[test]
+    fn other_keys() {
+        let encoder = InputEncoder::new();
+        let encode = |key| encoder.encode_key(key, Modifiers::none());
+        let shift = Modifiers {
+            shift: true,
+            ..Modifiers::none()
+        };
+        assert_eq!(encode(Key::F(1)), Some(b"\x1bOP".to_vec()));
+        assert_eq!(
+            encoder.encode_key(Key::F(2), shift),
+            Some(b"\x1b[1;2Q".to_vec())
+        );
+        assert_eq!(encode(Key::F(12)), Some(b"\x1b[24~".to_vec()));
+        assert_eq!(
+            encoder.encode_key(Key::Delete, shift),
+            Some(b"\x1b[3;2~".to_vec())
+        );
+        assert_eq!(encode(Key::Ctrl('c')), Some(vec![0x03]));
+        assert_eq!(encode(Key::Alt('x')), Some(b"\x1bx".to_vec()));
+        assert_eq!(encode(Key::Char('\n')), Some(b"\r".to_vec()));
+        assert_eq!(encode(Key::F(13)), None);
+    }
+
+    #[test]
+    fn kitty_keyboard_protocol() {
+        let mut encoder = InputEncoder::new();
+        let encode = |encoder: &InputEncoder, key| encoder.encode_key(key, Modifiers::none());
+        encoder.modes.keyboard_flags = KITTY_DISAMBIGUATE;
+        assert_eq!(encode(&encoder, Key::Char('a')), Some(b"a".to_vec()));
+        assert_eq!(encode(&encoder, Key::Char('A')), Some(b"A".to_vec()));
+        assert_eq!(encode(&encoder, Key::Char('\t')), Some(b"\t".to_vec()));
+        assert_eq!(encode(&encoder, Key::Esc), Some(b"\x1b[27u".to_vec()));
+        assert_eq!(
+            encode(&encoder, Key::Ctrl('i')),
+            Some(b"\x1b[105;5u".to_vec())
+        );
+        assert_eq!(
+            encode(&encoder, Key::Alt('X')),
+            Some(b"\x1b[120;4u".to_vec())
+        );
+        assert_eq!(encode(&encoder, Key::Up), Some(b"\x1b[A".to_vec()));
+
+        encoder.modes.keyboard_flags = KITTY_REPORT_ALL_KEYS;
+        assert_eq!(encode(&encoder, Key::Char('a')), Some(b"\x1b[97u".to_vec()));
+        assert_eq!(
+            encode(&encoder, Key::Char('\n')),
+            Some(b"\x1b[13u".to_vec())
+        );
+        let input = Input {
+            event: Event::Key(Key::Char('\t')),
+            raw: b"\t".to_vec(),
+        };
+        assert_eq!(encoder.encode_input(&input), b"\x1b[9u".to_vec());
+    }
+
+    #[test]
+    fn modify_other_keys() {
+        let mut encoder = InputEncoder::new();
+        let encode = |encoder: &InputEncoder, key| encoder.encode_key(key, Modifiers::none());
+        encoder.modes.modify_other_keys = ModifyOtherKeys::EnableExceptWellDefined;
+        assert_eq!(encode(&encoder, Key::Ctrl('c')), Some(vec![0x03]));
+        assert_eq!(
+            encode(&encoder, Key::Ctrl('1')),
+            Some(b"\x1b[27;5;49~".to_vec())
+        );
+        assert_eq!(encode(&encoder, Key::Alt('x')), Some(b"\x1bx".to_vec()));
+
+        encoder.modes.modify_other_keys = ModifyOtherKeys::EnableAll;
+        assert_eq!(
+            encode(&encoder, Key::Ctrl('c')),
+            Some(b"\x1b[27;5;99~".to_vec())
+        );
+        assert_eq!(
+            encode(&encoder, Key::Alt('x')),
+            Some(b"\x1b[27;3;120~".to_vec())
+        );
+        assert_eq!(encode(&encoder, Key::Char('X')), Some(b"X".to_vec()));
+    }
+
+    #[test]
+    fn keypad() {
+        let mut encoder = InputEncoder::new();
+        assert_eq!(
+            encoder.encode_keypad_key(KeypadKey::Digit(5), Modifiers::none()),
+            b"5".to_vec()
+        );
+        assert_eq!(
+            encoder.encode_keypad_key(KeypadKey::Enter, Modifiers::none()),
+            b"\r".to_vec()
+        );
+        encoder.modes.application_keypad = true;
+        assert_eq!(
+            encoder.encode_keypad_key(KeypadKey::Digit(5), Modifiers::none()),
+            b"\x1bOu".to_vec()
+        );
+        assert_eq!(
+            encoder.encode_keypad_key(KeypadKey::Enter, Modifiers::none()),
+            b"\x1bOM".to_vec()
+        );
+    }
+
+    #[test]
+    fn paste() {
+        let mut encoder = InputEncoder::new();
+        assert_eq!(encoder.encode_paste("a\nb\r\nc"), b"a\rb\rc".to_vec());
+        encoder.modes.bracketed_paste = true;
+        assert_eq!(
+            encoder.encode_paste("a\nb"),
+            b"\x1b[200~a\nb\x1b[201~".to_vec()
+        );
+        assert_eq!(
+            encoder.encode_paste("a\x1b[20\x1b[201~1~b"),
+            b"\x1b[200~ab\x1b[201~".to_vec()
+        );
+    }
+
+    #[test]
+    fn focus() {
+        let mut encoder = InputEncoder::new();
+        assert_eq!(encoder.encode_focus(true), None);
+        encoder.modes.focus_events = true;
+        assert_eq!(encoder.encode_focus(true), Some(b"\x1b[I".to_vec()));
+        assert_eq!(encoder.encode_focus(false), Some(b"\x1b[O".to_vec()));
+    }
+
+    #[test]
+    fn mouse_disabled() {
+        let mut encoder = InputEncoder::new();
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Press(MouseButton::Left, 1, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn mouse_default_encoding() {
+        let mut encoder = encoder(MouseProtocol::Clicks, MouseEncoding::Default);
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Press(MouseButton::Right, 1, 2)),
+            Some(b"\x1b[M\x22\x21\x22".to_vec())
+        );
+        assert_eq!(encoder.encode_mouse(MouseEvent::Hold(2, 2)), None);
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Release(2, 2)),
+            Some(b"\x1b[M\x23\x22\x22".to_vec())
+        );
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Press(MouseButton::WheelUp, 300, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn mouse_x10() {
+        let mut encoder = encoder(MouseProtocol::X10, MouseEncoding::Default);
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Press(MouseButton::Left, 1, 1)),
+            Some(b"\x1b[M\x20\x21\x21".to_vec())
+        );
+        assert_eq!(encoder.encode_mouse(MouseEvent::Release(1, 1)), None);
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Press(MouseButton::WheelDown, 1, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn mouse_sgr_encoding() {
+        let mut encoder = encoder(MouseProtocol::ButtonMotion, MouseEncoding::Sgr);
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Press(MouseButton::Middle, 300, 4)),
+            Some(b"\x1b[<1;300;4M".to_vec())
+        );
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Hold(301, 4)),
+            Some(b"\x1b[<33;301;4M".to_vec())
+        );
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Release(301, 4)),
+            Some(b"\x1b[<1;301;4m".to_vec())
+        );
+        assert_eq!(encoder.encode_mouse(MouseEvent::Hold(302, 4)), None);
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Press(MouseButton::WheelDown, 5, 6)),
+            Some(b"\x1b[<65;5;6M".to_vec())
+        );
+    }
+
+    #[test]
+    fn mouse_urxvt_encoding() {
+        let mut encoder = encoder(MouseProtocol::AnyMotion, MouseEncoding::Urxvt);
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Hold(7, 8)),
+            Some(b"\x1b[67;7;8M".to_vec())
+        );
+        assert_eq!(
+            encoder.encode_mouse(MouseEvent::Press(MouseButton::Left, 7, 8)),
+            Some(b"\x1b[32;7;8M".to_vec())
+        );
+    }
+}
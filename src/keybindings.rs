@@ -0,0 +1,145 @@
+//! Configurable key bindings of the `Container` implementation of `Terminal`.
+use input::{InputEncoder, Modifiers};
+use unsegen::input::{Event, Key, ScrollBehavior, Scrollable, ToEvent};
+
+/// Create the event for a key press with modifiers.
+///
+/// termion does not understand modified special keys (e.g., Shift+PageUp), so these are
+/// represented as unsupported events containing the sequence that xterm-like host terminals send.
+pub fn modified_key(key: Key, modifiers: Modifiers) -> Event {
+    if modifiers == Modifiers::none() {
+        return Event::Key(key);
+    }
+    match InputEncoder::new().encode_key(key, modifiers) {
+        Some(bytes) => Event::Unsupported(bytes),
+        None => Event::Key(key),
+    }
+}
+
+/// Events that trigger scrolling in the scrollback buffer of the terminal.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScrollBindings {
+    forwards: Vec<Event>,
+    backwards: Vec<Event>,
+    to_beginning: Vec<Event>,
+    to_end: Vec<Event>,
+}
+
+impl ScrollBindings {
+    /// Create bindings without any triggers. Add triggers using the other functions!
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scroll forwards on the provided event.
+    pub fn forwards_on<E: ToEvent>(mut self, event: E) -> Self {
+        self.forwards.push(event.to_event());
+        self
+    }
+
+    /// Scroll backwards on the provided event.
+    pub fn backwards_on<E: ToEvent>(mut self, event: E) -> Self {
+        self.backwards.push(event.to_event());
+        self
+    }
+
+    /// Scroll to the beginning of the scrollback buffer on the provided event.
+    pub fn to_beginning_on<E: ToEvent>(mut self, event: E) -> Self {
+        self.to_beginning.push(event.to_event());
+        self
+    }
+
+    /// Scroll to the end of the scrollback buffer on the provided event.
+    pub fn to_end_on<E: ToEvent>(mut self, event: E) -> Self {
+        self.to_end.push(event.to_event());
+        self
+    }
+
+    /// Check whether the event triggers any kind of scrolling.
+    pub(crate) fn is_bound(&self, event: &Event) -> bool {
+        self.forwards
+            .iter()
+            .chain(self.backwards.iter())
+            .chain(self.to_beginning.iter())
+            .chain(self.to_end.iter())
+            .any(|e| e == event)
+    }
+
+    /// Create a behavior that scrolls the provided `Scrollable` according to the bindings.
+    pub(crate) fn behavior<'a, S: Scrollable>(
+        &self,
+        scrollable: &'a mut S,
+    ) -> ScrollBehavior<'a, S> {
+        let mut behavior = ScrollBehavior::new(scrollable);
+        for event in self.forwards.iter() {
+            behavior = behavior.forwards_on(event.clone());
+        }
+        for event in self.backwards.iter() {
+            behavior = behavior.backwards_on(event.clone());
+        }
+        for event in self.to_beginning.iter() {
+            behavior = behavior.to_beginning_on(event.clone());
+        }
+        for event in self.to_end.iter() {
+            behavior = behavior.to_end_on(event.clone());
+        }
+        behavior
+    }
+}
+
+/// Key bindings of the `Container` implementation of `Terminal`.
+///
+/// Scrolling bindings (including the scroll mode prefix) only apply if the program running in the
+/// terminal does not use the alternate screen, mouse reporting or application cursor keys, i.e.,
+/// when it does not look like a full screen application. Otherwise, all input is passed on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    pub(crate) scroll: ScrollBindings,
+    pub(crate) scroll_mode_prefix: Option<Event>,
+    pub(crate) scroll_mode: ScrollBindings,
+}
+
+impl KeyBindings {
+    /// Create key bindings without any bindings, i.e., all input is passed on to the terminal.
+    pub fn new() -> Self {
+        KeyBindings {
+            scroll: ScrollBindings::new(),
+            scroll_mode_prefix: None,
+            scroll_mode: ScrollBindings::new(),
+        }
+    }
+
+    /// Set the bindings for scrolling that are always active.
+    pub fn scroll(mut self, bindings: ScrollBindings) -> Self {
+        self.scroll = bindings;
+        self
+    }
+
+    /// Enter a scroll mode on the provided event.
+    ///
+    /// In scroll mode, all input is consumed. The provided bindings are used for scrolling and all
+    /// other input leaves the scroll mode (and scrolls back to the end).
+    pub fn scroll_mode<E: ToEvent>(mut self, prefix: E, bindings: ScrollBindings) -> Self {
+        self.scroll_mode_prefix = Some(prefix.to_event());
+        self.scroll_mode = bindings;
+        self
+    }
+}
+
+/// Scroll using `Shift+PageUp`/`Shift+PageDown` and jump to the beginning/end using
+/// `Shift+Home`/`Shift+End`.
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let shift = Modifiers {
+            shift: true,
+            ..Modifiers::none()
+        };
+        KeyBindings::new().scroll(
+            ScrollBindings::new()
+                .forwards_on(modified_key(Key::PageDown, shift))
+                .backwards_on(modified_key(Key::PageUp, shift))
+                .to_beginning_on(modified_key(Key::Home, shift))
+                .to_end_on(modified_key(Key::End, shift)),
+        )
+    }
+}
@@ -36,6 +36,9 @@
 //!
 //!         // When you write to the created pty, the input should appear on screen!
 //!         term_widget.add_byte_input(&bytes);
+//!         for event in term_widget.drain_events() {
+//!             // React to title changes, bells, etc. here...
+//!         }
 //!         {
 //!             let win = term.create_root_window();
 //!
@@ -56,25 +59,45 @@ extern crate unsegen;
 extern crate vte;
 #[allow(dead_code)]
 mod ansi;
+mod clipboard;
+mod event;
 #[allow(dead_code)]
 mod index;
+mod input;
+mod keybindings;
+mod palette;
+mod process;
 mod pty;
 mod terminalwindow;
 
 use ansi::Processor;
+use log::warn;
 use pty::{PTYInput, PTYOutput, PTY};
 use std::ffi::{OsStr, OsString};
 use unsegen::base::basic_types::*;
-use unsegen::base::Window;
+use unsegen::base::{StyleModifier, Window};
 use unsegen::container::Container;
-use unsegen::input::{Behavior, Input, Key, OperationResult, ScrollBehavior, Scrollable, Writable};
+use unsegen::input::{Behavior, Input, Key, MouseEvent, OperationResult, Scrollable, Writable};
 use unsegen::widget::{Demand2D, RenderingHints, Widget};
 
 use terminalwindow::DualWindow;
+pub use terminalwindow::ShellCommand;
+
+pub use ansi::{ClipboardSelection, Hyperlink, WorkingDirectory};
+pub use clipboard::ClipboardPolicy;
+pub use event::TerminalEvent;
+pub use input::{KeypadKey, Modifiers};
+pub use keybindings::{modified_key, KeyBindings, ScrollBindings};
+pub use palette::{Palette, Rgb};
+pub use process::Signal;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::thread;
+use std::time::{Duration, Instant};
 
 fn read_slave_input_loop<S: SlaveInputSink>(mut sink: S, mut reader: PTYOutput) {
     use std::io::Read;
@@ -96,7 +119,10 @@ pub trait SlaveInputSink: std::marker::Send {
     fn receive_bytes_from_pty(&mut self, data: Box<[u8]>);
 }
 
-/// An unsegen `Behavior` that passes all (raw!) inputs through to the modelled terminal.
+/// An unsegen `Behavior` that passes all inputs through to the modelled terminal.
+///
+/// Inputs are passed on raw, except for keys whose encoding depends on the modes requested by the
+/// program running in the terminal (e.g., cursor keys).
 pub struct PassthroughBehavior<'a> {
     term: &'a mut Terminal,
 }
@@ -109,8 +135,10 @@ impl<'a> PassthroughBehavior<'a> {
 
 impl<'a> Behavior for PassthroughBehavior<'a> {
     fn input(self, i: Input) -> Option<Input> {
-        self.term.process_input(i);
-        None
+        match self.term.process_input(&i) {
+            Ok(()) => None,
+            Err(_) => Some(i),
+        }
     }
 }
 
@@ -118,7 +146,8 @@ impl<'a> Behavior for PassthroughBehavior<'a> {
 /// drawn.
 ///
 /// Use `ScrollBehavior` to scroll in the (potentially infinite buffer) and `WriteBehavior` to
-/// pass specific keystrokes to the terminal.
+/// pass specific keystrokes to the terminal. Alternatively, configure the `KeyBindings` of the
+/// `Container` implementation.
 pub struct Terminal {
     terminal_window: RefCell<DualWindow>,
     //slave_input_thread: thread::Thread,
@@ -130,6 +159,14 @@ pub struct Terminal {
     slave_name: OsString,
 
     ansi_processor: Processor,
+
+    key_bindings: KeyBindings,
+    scroll_mode: bool,
+
+    closed: Cell<bool>,
+
+    visual_bell: Option<Duration>,
+    last_bell: Option<Instant>,
 }
 
 impl Terminal {
@@ -138,8 +175,8 @@ impl Terminal {
     ///
     /// This method will create a posix pty. The associated file (path) can be obtained using
     /// `get_slave_name`.
-    pub fn new<S: SlaveInputSink + 'static>(input_sink: S) -> std::io::Result<Self> {
-        let process_pty = PTY::open().expect("Could not create pty.");
+    pub fn new<S: SlaveInputSink + 'static>(input_sink: S) -> io::Result<Self> {
+        let process_pty = PTY::open()?;
 
         let ptsname = process_pty.name().to_owned();
 
@@ -169,11 +206,18 @@ impl Terminal {
             _slave_handle: pts,
             slave_name: ptsname,
             ansi_processor: Processor::new(),
+            key_bindings: KeyBindings::default(),
+            scroll_mode: false,
+            closed: Cell::new(false),
+            visual_bell: None,
+            last_bell: None,
         })
     }
 
     /// Add _raw_ byte input to the terminal window. Call this for bytes that you received
     /// (indirectly) from SlaveInputSink::receive_bytes_from_pty.
+    ///
+    /// Afterwards, retrieve the events that resulted from the input using `drain_events`.
     pub fn add_byte_input(&mut self, bytes: &[u8]) {
         use std::ops::DerefMut;
         let mut window_ref = self.terminal_window.borrow_mut();
@@ -184,6 +228,197 @@ impl Terminal {
         }
     }
 
+    /// Retrieve (and remove) all events that occurred since the last call, oldest first.
+    ///
+    /// Events are not discarded automatically, so this should be called regularly, e.g., after
+    /// every call to `add_byte_input`.
+    pub fn drain_events(&mut self) -> Vec<TerminalEvent> {
+        let events = self.terminal_window.get_mut().drain_events();
+        if events.contains(&TerminalEvent::Bell) {
+            self.last_bell = Some(Instant::now());
+        }
+        events
+    }
+
+    /// Enable (or disable with `None`) the visual bell: For the given duration after the bell has
+    /// been rung (and the `Bell` event has been retrieved using `drain_events`), the terminal is
+    /// drawn inverted.
+    ///
+    /// The `Terminal` does not redraw itself, so the application has to redraw after the duration
+    /// has passed (see `visual_bell_active`).
+    pub fn set_visual_bell(&mut self, duration: Option<Duration>) {
+        self.visual_bell = duration;
+    }
+
+    /// Check whether the terminal is currently drawn inverted because of the visual bell.
+    pub fn visual_bell_active(&self) -> bool {
+        match (self.visual_bell, self.last_bell) {
+            (Some(duration), Some(last_bell)) => last_bell.elapsed() < duration,
+            _ => false,
+        }
+    }
+
+    /// Get the window title as set by the program running in the terminal.
+    pub fn title(&self) -> String {
+        self.terminal_window.borrow().titles().title.clone()
+    }
+
+    /// Get the icon name (i.e., the short title, e.g., for tabs) as set by the program running in
+    /// the terminal.
+    pub fn icon_name(&self) -> String {
+        self.terminal_window.borrow().titles().icon_name.clone()
+    }
+
+    /// Set the palette that is used to resolve indexed colors as well as the default foreground,
+    /// background and cursor colors.
+    ///
+    /// Changes of colors made by the program running in the terminal are discarded. Queries of the
+    /// program for the default colors are only answered if they are set in the palette (see
+    /// `Palette`).
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.terminal_window.get_mut().set_palette(palette);
+    }
+
+    /// Get the palette that is currently used to resolve colors. This includes changes made by the
+    /// program running in the terminal.
+    pub fn palette(&self) -> Palette {
+        *self.terminal_window.borrow().palette()
+    }
+
+    /// Set the size of a single cell (i.e., character) in pixels, or 0 if unknown (the default).
+    ///
+    /// This is used to report the size of the window in pixels to the program running in the
+    /// terminal, which, e.g., image viewers rely on.
+    pub fn set_cell_pixel_size(&mut self, width: u16, height: u16) -> io::Result<()> {
+        if self
+            .terminal_window
+            .get_mut()
+            .set_cell_pixel_size(width, height)
+        {
+            self.resize_pty()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Advance the blink phase of the terminal, i.e., toggle the visibility of blinking text.
+    ///
+    /// The `Terminal` does not keep track of time itself, so this has to be called periodically by
+    /// the application (followed by a redraw) if blinking text is to be supported. Text that
+    /// blinks rapidly toggles on every call, slowly blinking text on every second call.
+    pub fn tick_blink(&mut self) {
+        self.terminal_window.get_mut().advance_blink_phase();
+    }
+
+    /// Report a mouse event to the program running in the terminal.
+    ///
+    /// The (one-based) coordinates of the event have to be relative to the top left cell of the
+    /// widget, i.e., the top left cell has coordinates (1, 1). The event is only reported if the
+    /// program has requested mouse reporting (and the event lies within the widget), which is
+    /// indicated by the return value. Unreported events can be used otherwise by the application,
+    /// e.g., for scrolling.
+    ///
+    /// Fails if the event could not be written to the pty (see `is_closed`).
+    pub fn report_mouse_event(&mut self, event: MouseEvent) -> io::Result<bool> {
+        let window = self.terminal_window.get_mut();
+        let (x, y) = match event {
+            MouseEvent::Press(_, x, y) | MouseEvent::Release(x, y) | MouseEvent::Hold(x, y) => {
+                (x, y)
+            }
+        };
+        if x as i32 > window.get_width().raw_value() || y as i32 > window.get_height().raw_value() {
+            return Ok(false);
+        }
+        match window.input_encoder_mut().encode_mouse(event) {
+            Some(bytes) => {
+                self.write_to_pty(&bytes)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Get the hyperlink (as specified by the program running in the terminal using OSC 8) of the
+    /// cell at the given position.
+    ///
+    /// The (zero-based) coordinates have to be relative to the top left cell of the widget as it
+    /// was last drawn. Note that the coordinates of termion mouse events are one-based.
+    pub fn hyperlink_at(&self, x: ColIndex, y: RowIndex) -> Option<Hyperlink> {
+        self.terminal_window.borrow().hyperlink_at(x, y).cloned()
+    }
+
+    /// Set which kind of access to the clipboard (via OSC 52) is granted to the program running in
+    /// the terminal.
+    pub fn set_clipboard_policy(&mut self, policy: ClipboardPolicy) {
+        self.terminal_window.get_mut().set_clipboard_policy(policy);
+    }
+
+    /// Answer a `TerminalEvent::ClipboardRequest` with the contents of the requested selection.
+    ///
+    /// Fails if reading the clipboard is not allowed by the `ClipboardPolicy`.
+    pub fn answer_clipboard_request(
+        &mut self,
+        selection: ClipboardSelection,
+        contents: &str,
+    ) -> io::Result<()> {
+        if !self.terminal_window.get_mut().clipboard_policy().allow_read {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "reading the clipboard is not allowed",
+            ));
+        }
+        let answer = format!(
+            "\x1b]52;{};{}\x07",
+            selection.to_char(),
+            clipboard::encode_base64(contents.as_bytes())
+        );
+        self.write_to_pty(answer.as_bytes())
+    }
+
+    /// Get the commands that have been marked by the shell running in the terminal (using OSC 133
+    /// shell integration), oldest first.
+    pub fn commands(&self) -> Vec<ShellCommand> {
+        self.terminal_window.borrow().commands()
+    }
+
+    /// Get the text of the given lines (e.g., the output of a `ShellCommand`) of the buffer of the
+    /// terminal, separated by newlines.
+    pub fn text(&self, lines: Range<usize>) -> String {
+        self.terminal_window.borrow().text(lines)
+    }
+
+    /// Scroll up to the closest prompt (see `commands`) above the top of the widget.
+    ///
+    /// Returns whether there is such a prompt (c.f. `Scrollable::scroll_backwards`).
+    pub fn scroll_to_previous_prompt(&mut self) -> bool {
+        self.terminal_window
+            .get_mut()
+            .scroll_to_previous_prompt()
+            .is_ok()
+    }
+
+    /// Scroll down to the closest prompt (see `commands`) below the top of the widget.
+    ///
+    /// Returns whether there is such a prompt and scrolling changed the view (c.f.
+    /// `Scrollable::scroll_forwards`).
+    pub fn scroll_to_next_prompt(&mut self) -> bool {
+        self.terminal_window
+            .get_mut()
+            .scroll_to_next_prompt()
+            .is_ok()
+    }
+
+    /// Set the key bindings that are used by the `Container` implementation.
+    pub fn set_key_bindings(&mut self, key_bindings: KeyBindings) {
+        self.key_bindings = key_bindings;
+        self.scroll_mode = false;
+    }
+
+    /// Check whether the terminal is in scroll mode (see `KeyBindings::scroll_mode`).
+    pub fn in_scroll_mode(&self) -> bool {
+        self.scroll_mode
+    }
+
     /// Get the name of the slave pseudoterminal that is associated with the `Terminal`.
     ///
     /// (c.f. posix `ptsname`)
@@ -191,37 +426,181 @@ impl Terminal {
         self.slave_name.as_ref()
     }
 
-    /// Forward the raw input to the terminal.
-    fn process_input(&mut self, i: Input) {
+    /// Get the id of the foreground process group of the slave pseudoterminal, i.e., of the
+    /// program that currently receives input from the terminal.
+    ///
+    /// (c.f. posix `tcgetpgrp`)
+    pub fn foreground_process_group(&self) -> io::Result<libc::pid_t> {
+        self.master_input_sink.borrow().foreground_process_group()
+    }
+
+    /// Get the command line of the foreground process (group leader) of the slave pseudoterminal.
+    ///
+    /// This is currently only supported on Linux.
+    pub fn foreground_process_command_line(&self) -> io::Result<Vec<OsString>> {
+        process::command_line(self.foreground_process_group()?)
+    }
+
+    /// Get the working directory of the foreground process (group leader) of the slave
+    /// pseudoterminal.
+    ///
+    /// If it cannot be determined directly (which is currently only supported on Linux), the
+    /// directory most recently reported by the program running in the terminal (via OSC 7) is
+    /// returned, if any and if it resides on this machine.
+    pub fn foreground_process_working_directory(&self) -> io::Result<PathBuf> {
+        let res = self
+            .foreground_process_group()
+            .and_then(process::working_directory);
+        match (res, self.working_directory()) {
+            (Err(_), Some(ref reported)) if reported.is_local() => Ok(reported.path.clone()),
+            (res, _) => res,
+        }
+    }
+
+    /// Get the working directory most recently reported by the program running in the terminal
+    /// (usually the shell, using OSC 7).
+    ///
+    /// Changes are announced using `TerminalEvent::WorkingDirectoryChanged`. Note that the
+    /// directory may reside on a different host, e.g., when the shell runs via ssh.
+    pub fn working_directory(&self) -> Option<WorkingDirectory> {
+        self.terminal_window.borrow().working_directory().cloned()
+    }
+
+    /// Send a signal (e.g., `Signal::Interrupt`) to the foreground process group of the slave
+    /// pseudoterminal.
+    ///
+    /// In contrast to writing the corresponding control character (e.g., `^C`) to the terminal,
+    /// this also works if the program has disabled signal generation (e.g., in raw mode).
+    pub fn signal_foreground_process_group(&self, signal: Signal) -> io::Result<()> {
+        process::signal_process_group(self.foreground_process_group()?, signal)
+    }
+
+    /// Send a key press to the program running in the terminal, encoded according to the modes it
+    /// has requested (e.g., application cursor keys).
+    pub fn send_key(&mut self, key: Key, modifiers: Modifiers) -> io::Result<()> {
+        let bytes = self
+            .terminal_window
+            .get_mut()
+            .input_encoder()
+            .encode_key(key, modifiers);
+        match bytes {
+            Some(bytes) => self.write_to_pty(&bytes),
+            None => Ok(()),
+        }
+    }
+
+    /// Send a key press on the numeric keypad to the program running in the terminal, encoded
+    /// according to the keypad mode it has requested.
+    pub fn send_keypad_key(&mut self, key: KeypadKey, modifiers: Modifiers) -> io::Result<()> {
+        let bytes = self
+            .terminal_window
+            .get_mut()
+            .input_encoder()
+            .encode_keypad_key(key, modifiers);
+        self.write_to_pty(&bytes)
+    }
+
+    /// Paste text into the terminal.
+    ///
+    /// If the program running in the terminal has enabled bracketed paste mode, the text is
+    /// enclosed in paste markers (so that, e.g., a shell does not execute pasted lines
+    /// immediately). Otherwise, newlines are sent as carriage returns, just like when typing.
+    pub fn paste(&mut self, text: &str) -> io::Result<()> {
+        let bytes = self
+            .terminal_window
+            .get_mut()
+            .input_encoder()
+            .encode_paste(text);
+        self.write_to_pty(&bytes)
+    }
+
+    /// Tell the terminal whether it has the focus of the application.
+    ///
+    /// Focus changes are reported to the program running in the terminal if it requested so. An
+    /// unfocused terminal draws its (block) cursor as an underline.
+    pub fn set_focused(&mut self, focused: bool) -> io::Result<()> {
+        let window = self.terminal_window.get_mut();
+        if !window.set_focused(focused) {
+            return Ok(());
+        }
+        match window.input_encoder().encode_focus(focused) {
+            Some(bytes) => self.write_to_pty(&bytes),
+            None => Ok(()),
+        }
+    }
+
+    /// Check whether the pty has been found to be closed, i.e., a write to (or resize of) the pty
+    /// failed. This is usually the case when the program running in the terminal has exited.
+    ///
+    /// A closed terminal does not consume any input in its `Container` implementation.
+    pub fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    /// Forward the input to the terminal.
+    fn process_input(&mut self, i: &Input) -> io::Result<()> {
+        let bytes = self
+            .terminal_window
+            .get_mut()
+            .input_encoder()
+            .encode_input(i);
+        self.write_to_pty(&bytes)
+    }
+
+    fn write_to_pty(&mut self, bytes: &[u8]) -> io::Result<()> {
         use std::io::Write;
-        self.master_input_sink
-            .borrow_mut()
-            .write_all(i.raw.as_slice())
-            .expect("Write to terminal");
+        let res = self.master_input_sink.get_mut().write_all(bytes);
+        if res.is_err() {
+            self.mark_closed();
+        }
+        res
+    }
+
+    fn mark_closed(&self) {
+        if !self.closed.replace(true) {
+            self.terminal_window
+                .borrow_mut()
+                .push_event(TerminalEvent::Closed);
+        }
     }
 
     /// Make sure that the underlying state of the terminal windows matches the specified size.
-    fn ensure_size(&self, w: Width, h: Height) {
+    fn ensure_size(&self, w: Width, h: Height) -> io::Result<()> {
         let mut window = self.terminal_window.borrow_mut();
         if w != window.get_width() || h != window.get_height() {
             window.set_width(w);
             window.set_height(h);
+            drop(window);
+            return self.resize_pty();
+        }
+        Ok(())
+    }
 
-            let w16 = w.raw_value() as u16;
-            let h16 = h.raw_value() as u16;
-            self.master_input_sink
-                .borrow_mut()
-                .resize(w16, h16, w16 /* TODO ??*/, h16 /* TODO ??*/)
-                .expect("Resize pty");
+    /// Inform the program running in the terminal about the current size of the window.
+    fn resize_pty(&self) -> io::Result<()> {
+        let (w, h, w_px, h_px) = {
+            let window = self.terminal_window.borrow();
+            let (w_px, h_px) = window.pixel_size();
+            (
+                window.get_width().raw_value() as u16,
+                window.get_height().raw_value() as u16,
+                w_px,
+                h_px,
+            )
+        };
+        let res = self.master_input_sink.borrow().resize(w, h, w_px, h_px);
+        if res.is_err() {
+            self.mark_closed();
         }
+        res
     }
 }
 
 impl Writable for Terminal {
     fn write(&mut self, c: char) -> OperationResult {
-        use std::io::Write;
-        write!(self.master_input_sink.borrow_mut(), "{}", c).expect("Write key to terminal");
-        Ok(())
+        let mut buf = [0; 4];
+        self.write_to_pty(c.encode_utf8(&mut buf).as_bytes())
+            .map_err(|_| ())
     }
 }
 
@@ -248,27 +627,55 @@ impl<'a> Widget for TerminalWidget<'a> {
     fn space_demand(&self) -> Demand2D {
         self.term.terminal_window.borrow().space_demand()
     }
-    fn draw(&self, window: Window, hints: RenderingHints) {
-        self.term
-            .ensure_size(window.get_width(), window.get_height());
+    fn draw(&self, mut window: Window, hints: RenderingHints) {
+        if self.term.visual_bell_active() {
+            window.modify_default_style(StyleModifier::new().invert(true));
+            window.clear();
+        }
+        if let Err(e) = self
+            .term
+            .ensure_size(window.get_width(), window.get_height())
+        {
+            warn!("Failed to resize pty: {}", e);
+        }
         self.term.terminal_window.borrow_mut().draw(window, hints);
     }
 }
 
 /// Default container behavior:
 ///
-/// Scroll using `PageUp`/`PageDown`, jump to beginning/end using `Home`/`End` and pass all other
+/// Scroll according to the configured `KeyBindings` (by default: using `Shift+PageUp`/
+/// `Shift+PageDown`, jump to beginning/end using `Shift+Home`/`Shift+End`) and pass all other
 /// input to the slave terminal.
 impl<P: ?Sized> Container<P> for Terminal {
     fn input(&mut self, input: Input, _: &mut P) -> Option<Input> {
+        if self.is_closed() {
+            return Some(input);
+        }
+        if !self.terminal_window.get_mut().is_scrollback_useful() {
+            self.scroll_mode = false;
+            return input.chain(PassthroughBehavior::new(self)).finish();
+        }
+
+        // Scroll the window (rather than self) so that the bindings can be borrowed alongside it.
+        let bindings = &self.key_bindings;
+        let window = self.terminal_window.get_mut();
+        if self.scroll_mode {
+            if bindings.scroll_mode.is_bound(&input.event) {
+                // Scrolling beyond the ends is not an error in scroll mode.
+                let _ = input.chain(bindings.scroll_mode.behavior(window)).finish();
+            } else {
+                self.scroll_mode = false;
+                let _ = self.scroll_to_end();
+            }
+            return None;
+        }
+        if bindings.scroll_mode_prefix.as_ref() == Some(&input.event) {
+            self.scroll_mode = true;
+            return None;
+        }
         input
-            .chain(
-                ScrollBehavior::new(self)
-                    .forwards_on(Key::PageDown)
-                    .backwards_on(Key::PageUp)
-                    .to_beginning_on(Key::Home)
-                    .to_end_on(Key::End),
-            )
+            .chain(bindings.scroll.behavior(window))
             .chain(PassthroughBehavior::new(self))
             .finish()
     }
@@ -287,13 +694,14 @@ impl Terminal {
 mod test {
     use super::*;
     use unsegen::base::terminal::test::FakeTerminal;
-    use unsegen::base::GraphemeCluster;
+    use unsegen::base::{Color, Cursor, GraphemeCluster, StyleModifier};
+    use unsegen::input::Event;
 
     struct FakeSlaveInputSink;
     impl SlaveInputSink for FakeSlaveInputSink {
         fn receive_bytes_from_pty(&mut self, _: Box<[u8]>) {}
     }
-    fn test_terminal<F: Fn(&mut Terminal)>(window_dim: (u32, u32), after: &str, action: F) {
+    fn draw_terminal<F: Fn(&mut Terminal)>(window_dim: (u32, u32), action: F) -> FakeTerminal {
         let mut term = FakeTerminal::with_size(window_dim);
         {
             let mut window = term.create_root_window();
@@ -304,7 +712,32 @@ mod test {
             let tw: &dyn Container<()> = &tw;
             tw.as_widget().draw(window, RenderingHints::default());
         }
-        term.assert_looks_like(after);
+        term
+    }
+    fn test_terminal<F: Fn(&mut Terminal)>(window_dim: (u32, u32), after: &str, action: F) {
+        draw_terminal(window_dim, action).assert_looks_like(after);
+    }
+    fn expected_terminal<F: Fn(&mut Cursor<Window>)>(
+        window_dim: (u32, u32),
+        write: F,
+    ) -> FakeTerminal {
+        let mut term = FakeTerminal::with_size(window_dim);
+        {
+            let mut window = term.create_root_window();
+            window.fill(GraphemeCluster::try_from('_').unwrap());
+            let mut cursor = Cursor::new(&mut window);
+            write(&mut cursor);
+        }
+        term
+    }
+    /// Feed the input to the terminal and return what it replies to the program.
+    fn reply_to(term: &mut Terminal, input: &[u8]) -> Vec<u8> {
+        let mut reply = Vec::new();
+        for byte in input {
+            term.ansi_processor
+                .advance(term.terminal_window.get_mut(), *byte, &mut reply);
+        }
+        reply
     }
     #[test]
     fn test_terminal_window_simple() {
@@ -318,4 +751,706 @@ mod test {
 
         test_terminal((2, 2), "te|st", |w| w.write("te\nst"));
     }
+
+    #[test]
+    fn test_terminal_palette() {
+        let red = |cursor: &mut Cursor<Window>| {
+            cursor.set_style_modifier(StyleModifier::new().fg_color(Color::Red));
+            cursor.write("r");
+        };
+        let custom = |cursor: &mut Cursor<Window>| {
+            cursor.set_style_modifier(StyleModifier::new().fg_color(Color::Rgb {
+                r: 0x10,
+                g: 0x20,
+                b: 0x30,
+            }));
+            cursor.write("r");
+        };
+        assert!(draw_terminal((1, 1), |w| w.write("\x1b[31mr")) == expected_terminal((1, 1), red));
+        assert!(
+            draw_terminal((1, 1), |w| w.write("\x1b]4;1;rgb:10/20/30\x07\x1b[31mr"))
+                == expected_terminal((1, 1), custom)
+        );
+        assert!(
+            draw_terminal((1, 1), |w| {
+                let mut palette = Palette::new();
+                palette.set(
+                    1,
+                    Some(Rgb {
+                        r: 0x10,
+                        g: 0x20,
+                        b: 0x30,
+                    }),
+                );
+                w.set_palette(palette);
+                w.write("\x1b[31mr");
+            }) == expected_terminal((1, 1), custom)
+        );
+        assert!(
+            draw_terminal((1, 1), |w| w
+                .write("\x1b]4;1;rgb:10/20/30\x07\x1b]104\x07\x1b[31mr"))
+                == expected_terminal((1, 1), red)
+        );
+    }
+
+    #[test]
+    fn test_terminal_default_colors() {
+        let rgb = |r, g, b| Color::Rgb { r, g, b };
+        let fg_bg = |cursor: &mut Cursor<Window>| {
+            cursor.set_style_modifier(
+                StyleModifier::new()
+                    .fg_color(rgb(1, 1, 1))
+                    .bg_color(rgb(2, 2, 2)),
+            );
+            cursor.write("ab");
+        };
+        let set_palette = |w: &mut Terminal| {
+            let mut palette = Palette::new();
+            palette.set_foreground(Some(Rgb { r: 1, g: 1, b: 1 }));
+            palette.set_background(Some(Rgb { r: 2, g: 2, b: 2 }));
+            w.set_palette(palette);
+        };
+        assert!(
+            draw_terminal((2, 1), |w| {
+                set_palette(w);
+                w.write("a\x1b[31;41m\x1b[39;49mb");
+            }) == expected_terminal((2, 1), fg_bg)
+        );
+        assert!(
+            draw_terminal((2, 1), |w| w.write("\x1b]10;#010101;#020202\x07ab"))
+                == expected_terminal((2, 1), fg_bg)
+        );
+    }
+
+    #[test]
+    fn test_terminal_default_style() {
+        let draw_with_default_style = |input: &str| {
+            let mut term = FakeTerminal::with_size((3, 1));
+            {
+                let mut window = term.create_root_window();
+                window.modify_default_style(StyleModifier::new().fg_color(Color::Blue).bold(true));
+                window.clear();
+                let mut tw = Terminal::new(FakeSlaveInputSink).unwrap();
+                tw.terminal_window.get_mut().set_show_cursor(false);
+                tw.write(input);
+                let tw: &dyn Container<()> = &tw;
+                tw.as_widget().draw(window, RenderingHints::default());
+            }
+            term
+        };
+        let expected = expected_terminal((3, 1), |cursor| {
+            let default = StyleModifier::new().fg_color(Color::Blue).bold(true);
+            cursor.set_style_modifier(default.fg_color(Color::Red));
+            cursor.write("a");
+            cursor.set_style_modifier(default);
+            cursor.write("bc");
+        });
+        assert!(draw_with_default_style("\x1b[31ma\x1b[39mb\x1b[31m\x1b[0mc") == expected);
+        assert!(draw_with_default_style("\x1b[31;22ma\x1b[0mbc") == expected);
+    }
+
+    #[test]
+    fn test_terminal_unfocused_cursor() {
+        let cursor = |style: StyleModifier| {
+            expected_terminal((2, 1), move |cursor| {
+                cursor.write("a");
+                cursor.set_style_modifier(style);
+                cursor.write(" ");
+            })
+        };
+        let draw = |focused| {
+            draw_terminal((2, 1), move |w| {
+                w.terminal_window.get_mut().set_show_cursor(true);
+                w.set_focused(focused).unwrap();
+                w.write("a");
+            })
+        };
+        assert!(draw(true) == cursor(StyleModifier::new().invert(true)));
+        assert!(draw(false) == cursor(StyleModifier::new().underline(true)));
+    }
+
+    #[test]
+    fn test_terminal_key_bindings() {
+        let shift = Modifiers {
+            shift: true,
+            ..Modifiers::none()
+        };
+        let press = |w: &mut Terminal, event: Event| {
+            let input = Input {
+                event,
+                raw: Vec::new(),
+            };
+            let _ = Container::<()>::input(w, input, &mut ());
+        };
+        test_terminal((1, 1), "a", |w| {
+            w.write("a\r\nb");
+            press(w, modified_key(Key::PageUp, shift));
+        });
+        test_terminal((1, 1), "b", |w| {
+            w.write("a\r\nb");
+            press(w, Event::Key(Key::PageUp));
+        });
+        // Scrolling does not make sense for full screen applications.
+        test_terminal((1, 1), "b", |w| {
+            w.write("a\r\nb\x1b[?1h");
+            press(w, modified_key(Key::PageUp, shift));
+        });
+
+        let scroll_mode = |w: &mut Terminal| {
+            w.set_key_bindings(KeyBindings::new().scroll_mode(
+                Key::Ctrl('s'),
+                ScrollBindings::new().backwards_on(Key::Char('k')),
+            ));
+            w.write("a\r\nb\r\nc");
+            press(w, Event::Key(Key::Ctrl('s')));
+            press(w, Event::Key(Key::Char('k')));
+            press(w, Event::Key(Key::Char('k')));
+        };
+        test_terminal((1, 1), "a", scroll_mode);
+        test_terminal((1, 1), "c", |w| {
+            scroll_mode(w);
+            press(w, Event::Key(Key::Char('q')));
+            assert!(!w.in_scroll_mode());
+        });
+    }
+
+    #[test]
+    fn test_terminal_default_key_bindings_raw_input() {
+        // The default bindings rely on termion reporting what xterm-like host terminals send for
+        // the modified keys as unsupported events.
+        let shift = Modifiers {
+            shift: true,
+            ..Modifiers::none()
+        };
+        let read = |raw: &[u8]| {
+            let mut inputs = Input::read_all(raw).collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(inputs.len(), 1);
+            inputs.pop().unwrap()
+        };
+        for (key, raw) in [
+            (Key::PageUp, &b"\x1b[5;2~"[..]),
+            (Key::PageDown, b"\x1b[6;2~"),
+            (Key::Home, b"\x1b[1;2H"),
+            (Key::End, b"\x1b[1;2F"),
+        ]
+        .iter()
+        {
+            assert_eq!(read(raw).event, modified_key(*key, shift));
+        }
+        test_terminal((1, 1), "a", |w| {
+            w.write("a\r\nb");
+            let _ = Container::<()>::input(w, read(b"\x1b[5;2~"), &mut ());
+        });
+    }
+
+    #[test]
+    fn test_terminal_signal_foreground_process_group() {
+        use std::fs::OpenOptions;
+        use std::os::unix::process::{CommandExt, ExitStatusExt};
+        use std::process::{Command, Stdio};
+
+        let term = Terminal::new(FakeSlaveInputSink).unwrap();
+        let slave = || {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(term.slave_name())
+                .unwrap()
+        };
+        let mut command = Command::new("sleep");
+        command
+            .arg("60")
+            .stdin(Stdio::from(slave()))
+            .stdout(Stdio::from(slave()))
+            .stderr(Stdio::from(slave()));
+        // Make the terminal the controlling terminal of the child, which thus becomes the
+        // foreground process group.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let mut child = command.spawn().unwrap();
+        assert_eq!(
+            term.foreground_process_group().unwrap(),
+            child.id() as libc::pid_t
+        );
+
+        term.signal_foreground_process_group(Signal::Terminate)
+            .unwrap();
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGTERM));
+    }
+
+    #[test]
+    fn test_terminal_closed() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        let input = Input {
+            event: Event::Key(Key::Char('a')),
+            raw: vec![b'a'],
+        };
+        assert!(!term.is_closed());
+        assert!(Container::<()>::input(&mut term, input.clone(), &mut ()).is_none());
+
+        term.mark_closed();
+        term.mark_closed();
+        assert!(Container::<()>::input(&mut term, input, &mut ()).is_some());
+        assert_eq!(term.drain_events(), vec![TerminalEvent::Closed]);
+    }
+
+    #[test]
+    fn test_terminal_window_size_report() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        term.ensure_size(Width::new(80).unwrap(), Height::new(24).unwrap())
+            .unwrap();
+        term.set_cell_pixel_size(8, 16).unwrap();
+        let reply = reply_to(&mut term, b"\x1b[14t\x1b[16t\x1b[18t");
+        assert_eq!(reply, b"\x1b[4;384;640t\x1b[6;16;8t\x1b[8;24;80t".to_vec());
+    }
+
+    #[test]
+    fn test_terminal_status_strings() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        term.ensure_size(Width::new(80).unwrap(), Height::new(24).unwrap())
+            .unwrap();
+        let input: &[u8] = b"\x1bP$qm\x1b\\\
+            \x1b[1;3;31;48;5;100;4:3;58;5;9m\x1bP$qm\x1b\\\
+            \x1bP$qr\x1b\\\x1b[2;10r\x1bP$qr\x1b\\\
+            \x1b[5 q\x1bP$q q\x1b\\\
+            \x1bP$qx\x1b\\";
+        let reply = reply_to(&mut term, input);
+        assert_eq!(
+            String::from_utf8(reply).unwrap(),
+            "\x1bP1$r0m\x1b\\\
+             \x1bP1$r0;1;3;4:3;31;48;5;100;58;5;9m\x1b\\\
+             \x1bP1$r1;24r\x1b\\\x1bP1$r2;10r\x1b\\\
+             \x1bP1$r6 q\x1b\\\
+             \x1bP0$r\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_terminal_color_report() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        let input: &[u8] = b"\x1b]4;1;?\x07\x1b]10;?\x07\x1b]11;?\x1b\\\x1b]12;?\x07";
+        // Unset default colors of the host terminal are unknown.
+        let reply = reply_to(&mut term, input);
+        assert_eq!(
+            String::from_utf8(reply).unwrap(),
+            "\x1b]4;1;rgb:cdcd/0000/0000\x07"
+        );
+
+        let mut palette = Palette::new();
+        palette.set_foreground(Some(Rgb {
+            r: 0x10,
+            g: 0x20,
+            b: 0x30,
+        }));
+        palette.set_background(Some(Rgb {
+            r: 0xff,
+            g: 0xff,
+            b: 0xff,
+        }));
+        term.set_palette(palette);
+        let reply = reply_to(&mut term, input);
+        assert_eq!(
+            String::from_utf8(reply).unwrap(),
+            "\x1b]4;1;rgb:cdcd/0000/0000\x07\x1b]10;rgb:1010/2020/3030\x07\
+             \x1b]11;rgb:ffff/ffff/ffff\x1b\\\x1b]12;rgb:1010/2020/3030\x07"
+        );
+    }
+
+    #[test]
+    fn test_terminal_mode_report() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        let input: &[u8] = b"\x1b[?2004$p\x1b[?2004h\x1b[?2004$p\x1b[?25$p\
+            \x1b[?1006h\x1b[?1006$p\x1b[?1015$p\x1b[?7$p\x1b[?2026$p\x1b[4$p\
+            \x1b[?1234$p\x1b[1234$p";
+        let reply = reply_to(&mut term, input);
+        assert_eq!(
+            String::from_utf8(reply).unwrap(),
+            "\x1b[?2004;2$y\x1b[?2004;1$y\x1b[?25;1$y\
+             \x1b[?1006;1$y\x1b[?1015;2$y\x1b[?7;3$y\x1b[?2026;4$y\x1b[4;4$y\
+             \x1b[?1234;0$y\x1b[1234;0$y"
+        );
+    }
+
+    #[test]
+    fn test_terminal_termcap() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        let mut input = b"\x1bP+q544E;5463;666F6F\x1b\\".to_vec();
+        // Overly long requests are dropped.
+        input.extend_from_slice(b"\x1bP+q");
+        input.extend(b"54".iter().cycle().take(ansi::MAX_DCS_LEN + 2));
+        input.extend_from_slice(b"\x1b\\");
+        let reply = reply_to(&mut term, &input);
+        assert_eq!(
+            String::from_utf8(reply).unwrap(),
+            "\x1bP1+r544E=787465726D2D323536636F6C6F72\x1b\\\
+             \x1bP1+r5463\x1b\\\
+             \x1bP0+r666F6F\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_terminal_title() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        term.write("\x1b]0;vim\x07");
+        assert_eq!(term.title(), "vim");
+        assert_eq!(term.icon_name(), "vim");
+        term.write("\x1b[22;0t\x1b]2;a;b\x07\x1b]1;c\x07abc");
+        assert_eq!(term.title(), "a;b");
+        assert_eq!(term.icon_name(), "c");
+        term.write("\x1b[23;2t");
+        assert_eq!(term.title(), "vim");
+        assert_eq!(term.icon_name(), "c");
+        term.write("\x1b[23;0t");
+        assert_eq!(term.icon_name(), "c");
+
+        let change = |title: &str, icon_name: &str| TerminalEvent::TitleChanged {
+            title: title.to_owned(),
+            icon_name: icon_name.to_owned(),
+        };
+        assert_eq!(
+            term.drain_events(),
+            vec![
+                change("vim", "vim"),
+                change("a;b", "vim"),
+                change("a;b", "c"),
+                change("vim", "c")
+            ]
+        );
+        assert_eq!(term.drain_events(), vec![]);
+    }
+
+    #[test]
+    fn test_terminal_bell() {
+        let visual_bell = draw_terminal((2, 1), |w| {
+            w.write("\x07a");
+            assert_eq!(w.drain_events(), vec![TerminalEvent::Bell]);
+            assert!(!w.visual_bell_active());
+            w.set_visual_bell(Some(Duration::from_secs(3600)));
+            w.write("\x07\x07");
+            assert_eq!(
+                w.drain_events(),
+                vec![TerminalEvent::Bell, TerminalEvent::Bell]
+            );
+            assert!(w.visual_bell_active());
+        });
+
+        let mut expected = FakeTerminal::with_size((2, 1));
+        {
+            let mut window = expected.create_root_window();
+            window.modify_default_style(StyleModifier::new().invert(true));
+            window.clear();
+            Cursor::new(&mut window).write("a");
+        }
+        assert!(visual_bell == expected);
+    }
+
+    #[test]
+    fn test_terminal_hyperlinks() {
+        let link = |uri: &str| {
+            Some(Hyperlink {
+                id: None,
+                uri: uri.to_owned(),
+            })
+        };
+        let input = "a\x1b]8;;x\x07bcd\x1b[0me\x1b]8;;\x07f\x1b]8;;y\x07g";
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        term.ensure_size(Width::new(4).unwrap(), Height::new(3).unwrap())
+            .unwrap();
+        term.write(input);
+        let at = |x: i32, y: i32| term.hyperlink_at(ColIndex::new(x), RowIndex::new(y));
+        assert_eq!(at(0, 0), None);
+        assert_eq!(at(1, 0), link("x"));
+        assert_eq!(at(0, 1), link("x"));
+        assert_eq!(at(1, 1), None);
+        assert_eq!(at(2, 1), link("y"));
+        assert_eq!(at(3, 1), None);
+        assert_eq!(at(0, 2), None);
+        assert_eq!(at(4, 0), None);
+
+        let expected = expected_terminal((4, 3), |cursor| {
+            cursor.write("a");
+            cursor.set_style_modifier(StyleModifier::new().underline(true));
+            cursor.write("bcd");
+            cursor.wrap_line();
+            cursor.write("e");
+            cursor.set_style_modifier(StyleModifier::new());
+            cursor.write("f");
+            cursor.set_style_modifier(StyleModifier::new().underline(true));
+            cursor.write("g");
+        });
+        assert!(draw_terminal((4, 3), |w| w.write(input)) == expected);
+    }
+
+    #[test]
+    fn test_terminal_hyperlinks_reclaimed() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        term.ensure_size(Width::new(4).unwrap(), Height::new(2).unwrap())
+            .unwrap();
+        // Restating a link reuses its entry.
+        term.write("\x1b]8;;x\x07a\x1b]8;;\x07\x1b[H\x1b]8;;x\x07b\x1b]8;;\x07");
+        assert_eq!(term.terminal_window.borrow().hyperlink_capacity(), 1);
+        // Links of cells that have been overwritten are dropped.
+        for i in 0..1000 {
+            term.write(&format!("\x1b[H\x1b]8;;{}\x07a\x1b]8;;\x07", i));
+        }
+        assert!(term.terminal_window.borrow().hyperlink_capacity() <= 64);
+        let link = Hyperlink {
+            id: None,
+            uri: "999".to_owned(),
+        };
+        assert_eq!(
+            term.hyperlink_at(ColIndex::new(0), RowIndex::new(0)),
+            Some(link)
+        );
+        assert_eq!(term.hyperlink_at(ColIndex::new(1), RowIndex::new(0)), None);
+    }
+
+    #[test]
+    fn test_terminal_erase() {
+        // EL and ECH do not apply attributes or links to the erased cells.
+        let input = "\x1b[4;9m\x1b]8;;x\x1b\\abcd\x1b[2D\x1b[K\x1b[G\x1b[X";
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        term.ensure_size(Width::new(4).unwrap(), Height::new(1).unwrap())
+            .unwrap();
+        term.write(input);
+        let at = |x: i32| term.hyperlink_at(ColIndex::new(x), RowIndex::new(0));
+        assert_eq!(at(0), None);
+        assert!(at(1).is_some());
+        assert_eq!(at(2), None);
+        assert_eq!(at(3), None);
+
+        let expected = expected_terminal((4, 1), |cursor| {
+            cursor.write(" ");
+            cursor.set_style_modifier(StyleModifier::new().underline(true));
+            cursor.write("b\u{336}");
+            cursor.set_style_modifier(StyleModifier::new());
+            cursor.write("  ");
+        });
+        let actual = draw_terminal((4, 1), |w| {
+            w.ensure_size(Width::new(4).unwrap(), Height::new(1).unwrap())
+                .unwrap();
+            w.write(input);
+        });
+        assert!(actual == expected);
+    }
+
+    #[test]
+    fn test_terminal_clipboard() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        let long = "x".repeat(2000);
+        term.write(&format!(
+            "\x1b]52;c;Zm9v\x07\x1b]52;;{}\x1b\\\x1b]52;ps;YmFy\x07\x1b]52;c;?\x07",
+            clipboard::encode_base64(long.as_bytes())
+        ));
+        let set =
+            |selections: Vec<ClipboardSelection>, contents: &str| TerminalEvent::ClipboardSet {
+                selections,
+                contents: contents.to_owned(),
+            };
+        assert_eq!(
+            term.drain_events(),
+            vec![
+                set(vec![ClipboardSelection::Clipboard], "foo"),
+                set(vec![ClipboardSelection::Clipboard], &long),
+                set(
+                    vec![ClipboardSelection::Primary, ClipboardSelection::Select],
+                    "bar"
+                ),
+            ]
+        );
+        assert!(term
+            .answer_clipboard_request(ClipboardSelection::Clipboard, "foo")
+            .is_err());
+
+        term.set_clipboard_policy(ClipboardPolicy {
+            allow_write: true,
+            allow_read: true,
+            max_size: 2,
+        });
+        term.write("\x1b]52;c;Zm9v\x07\x1b]52;c;Zm8=\x07\x1b]52;p;?\x07");
+        assert_eq!(
+            term.drain_events(),
+            vec![
+                set(vec![ClipboardSelection::Clipboard], "fo"),
+                TerminalEvent::ClipboardRequest {
+                    selection: ClipboardSelection::Primary
+                },
+            ]
+        );
+        assert!(term
+            .answer_clipboard_request(ClipboardSelection::Primary, "foo")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_terminal_working_directory() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        assert_eq!(term.working_directory(), None);
+        term.write("\x1b]7;file://remote/home/a%20b\x07\x1b]7;file://remote/home/a%20b\x07");
+        let dir = WorkingDirectory {
+            host: "remote".to_owned(),
+            path: "/home/a b".into(),
+        };
+        assert_eq!(term.working_directory(), Some(dir.clone()));
+        assert_eq!(
+            term.drain_events(),
+            vec![TerminalEvent::WorkingDirectoryChanged(dir)]
+        );
+        term.write("\x1b]7;file:///tmp\x1b\\");
+        assert!(term.working_directory().unwrap().is_local());
+        assert_eq!(term.drain_events().len(), 1);
+    }
+
+    #[test]
+    fn test_terminal_prompt_marks() {
+        let prompt = |command: &str, output: &str, exit_code: &str| {
+            format!(
+                "\x1b]133;A\x07$ \x1b]133;B\x07{}\r\n\x1b]133;C\x07{}\x1b]133;D;{}\x07",
+                command, output, exit_code
+            )
+        };
+        let input = format!(
+            "{}{}\x1b]133;A\x07$ ",
+            prompt("ls", "a\r\nb\r\n", "0"),
+            prompt("false", "", "1")
+        );
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        term.ensure_size(Width::new(5).unwrap(), Height::new(2).unwrap())
+            .unwrap();
+        term.write(&input);
+
+        let commands = term.commands();
+        assert_eq!(
+            commands,
+            vec![
+                ShellCommand {
+                    prompt_line: 0,
+                    command_line: Some(0),
+                    output: Some(1..3),
+                    exit_code: Some(0),
+                    finished: true,
+                },
+                ShellCommand {
+                    prompt_line: 3,
+                    command_line: Some(3),
+                    output: Some(4..4),
+                    exit_code: Some(1),
+                    finished: true,
+                },
+                ShellCommand {
+                    prompt_line: 4,
+                    command_line: None,
+                    output: None,
+                    exit_code: None,
+                    finished: false,
+                },
+            ]
+        );
+        assert_eq!(term.text(commands[0].output.clone().unwrap()), "a\nb");
+        assert_eq!(term.text(0..1), "$ ls");
+
+        let draw = |term: &Terminal| {
+            let mut fake = FakeTerminal::with_size((5, 2));
+            {
+                let window = fake.create_root_window();
+                term.terminal_window.borrow_mut().set_show_cursor(false);
+                let term: &dyn Container<()> = term;
+                term.as_widget().draw(window, RenderingHints::default());
+            }
+            fake
+        };
+        // The second command wraps.
+        draw(&term).assert_looks_like("se   |$    ");
+        assert!(!term.scroll_to_next_prompt());
+        assert!(term.scroll_to_previous_prompt());
+        draw(&term).assert_looks_like("$ fal|se   ");
+        assert!(term.scroll_to_previous_prompt());
+        draw(&term).assert_looks_like("$ ls |a    ");
+        assert!(!term.scroll_to_previous_prompt());
+        assert!(term.scroll_to_next_prompt());
+        draw(&term).assert_looks_like("$ fal|se   ");
+        assert!(term.scroll_to_next_prompt());
+        draw(&term).assert_looks_like("se   |$    ");
+    }
+
+    #[test]
+    fn test_terminal_notifications() {
+        let mut term = Terminal::new(FakeSlaveInputSink).unwrap();
+        term.write(concat!(
+            "\x1b]9;Build finished; 0 errors\x07",
+            "\x1b]9;4;1;50\x07",
+            "\x1b]777;notify;make;done; ok\x1b\\",
+            "\x1b]777;notify;title\x07",
+        ));
+        let notification = |title: Option<&str>, body: &str| TerminalEvent::Notification {
+            title: title.map(|t| t.to_owned()),
+            body: body.to_owned(),
+        };
+        assert_eq!(
+            term.drain_events(),
+            vec![
+                notification(None, "Build finished; 0 errors"),
+                notification(Some("make"), "done; ok"),
+                notification(Some("title"), ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_terminal_hidden_and_blink() {
+        test_terminal((3, 1), "a c", |w| w.write("a\x1b[8mb\x1b[28mc"));
+        test_terminal((3, 1), "abc", |w| w.write("a\x1b[5mb\x1b[25mc"));
+        test_terminal((3, 1), "a c", |w| {
+            w.write("a\x1b[6mb\x1b[25mc");
+            w.tick_blink();
+        });
+        test_terminal((3, 1), "abc", |w| {
+            w.write("a\x1b[5mb\x1b[25mc");
+            w.tick_blink();
+        });
+        test_terminal((3, 1), "a c", |w| {
+            w.write("a\x1b[5mb\x1b[25mc");
+            w.tick_blink();
+            w.tick_blink();
+        });
+    }
+
+    #[test]
+    fn test_terminal_dim_and_strike() {
+        let dim = |cursor: &mut Cursor<Window>| {
+            cursor.set_style_modifier(
+                StyleModifier::new()
+                    .fg_color(Color::Rgb {
+                        r: 0x88,
+                        g: 0x88,
+                        b: 0x88,
+                    })
+                    .bg_color(Color::Rgb { r: 0, g: 0, b: 0 }),
+            );
+            cursor.write("d");
+        };
+        assert!(
+            draw_terminal((1, 1), |w| {
+                let mut palette = Palette::new();
+                palette.set_foreground(Some(Rgb {
+                    r: 0xcc,
+                    g: 0xcc,
+                    b: 0xcc,
+                }));
+                palette.set_background(Some(Rgb { r: 0, g: 0, b: 0 }));
+                w.set_palette(palette);
+                w.write("\x1b[2md");
+            }) == expected_terminal((1, 1), dim)
+        );
+        assert!(
+            draw_terminal((2, 1), |w| w.write("\x1b[9ms\x1b[29mt"))
+                == expected_terminal((2, 1), |cursor| cursor.write("s\u{336}t"))
+        );
+    }
 }
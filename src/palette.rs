@@ -0,0 +1,162 @@
+//! The color table of the modelled terminal.
+pub use ansi::Rgb;
+
+/// Number of entries in the indexed color table (16 named colors, 6x6x6 color cube, 24 greys).
+pub const NUM_INDEXED_COLORS: usize = 256;
+
+/// A table of the 256 indexed colors of a terminal as well as its default foreground, background
+/// and cursor colors.
+///
+/// Entries that are not set (`None`) are not resolved by the `Terminal`, but passed on to the
+/// host terminal, i.e., indexed colors will look like they would without the `Terminal` widget in
+/// between. Entries that are set are drawn using their rgb value.
+///
+/// Programs can query colors (OSC 4, 10, 11 and 12). Unset indexed colors are reported with their
+/// xterm default value. Queries for unset default foreground, background and cursor colors are not
+/// answered, as the colors of the host terminal are unknown and programs (e.g., editors choosing a
+/// light or dark theme) should rather fall back to their defaults than rely on a guess. Set the
+/// default colors to the ones of the host terminal (if known) to have them reported.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Palette {
+    entries: [Option<Rgb>; NUM_INDEXED_COLORS],
+    foreground: Option<Rgb>,
+    background: Option<Rgb>,
+    cursor: Option<Rgb>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            entries: [None; NUM_INDEXED_COLORS],
+            foreground: None,
+            background: None,
+            cursor: None,
+        }
+    }
+}
+
+impl Palette {
+    /// Create a palette without any set entries, i.e., all colors are taken from the host terminal.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a palette with all entries set to the default values of xterm.
+    pub fn xterm() -> Self {
+        let mut palette = Self::default();
+        for (index, entry) in palette.entries.iter_mut().enumerate() {
+            *entry = Some(xterm_color(index as u8));
+        }
+        palette
+    }
+
+    /// Set (`Some`) or unset (`None`) the entry with the specified index.
+    pub fn set(&mut self, index: u8, color: Option<Rgb>) {
+        self.entries[index as usize] = color;
+    }
+
+    /// Get the entry with the specified index, if it is set.
+    pub fn get(&self, index: u8) -> Option<Rgb> {
+        self.entries[index as usize]
+    }
+
+    /// Get the rgb value of the entry with the specified index.
+    ///
+    /// As we cannot know the actual value of unset entries, the xterm default value is reported in
+    /// this case.
+    pub fn rgb(&self, index: u8) -> Rgb {
+        self.get(index).unwrap_or_else(|| xterm_color(index))
+    }
+
+    /// Set (`Some`) or unset (`None`) the default foreground color.
+    pub fn set_foreground(&mut self, color: Option<Rgb>) {
+        self.foreground = color;
+    }
+
+    /// Get the default foreground color, if it is set.
+    pub fn foreground(&self) -> Option<Rgb> {
+        self.foreground
+    }
+
+    /// Get the rgb value of the default foreground color.
+    ///
+    /// If it is not set, the value of entry 7 (white) is assumed.
+    pub fn foreground_rgb(&self) -> Rgb {
+        self.foreground.unwrap_or_else(|| self.rgb(7))
+    }
+
+    /// Set (`Some`) or unset (`None`) the default background color.
+    pub fn set_background(&mut self, color: Option<Rgb>) {
+        self.background = color;
+    }
+
+    /// Get the default background color, if it is set.
+    pub fn background(&self) -> Option<Rgb> {
+        self.background
+    }
+
+    /// Get the rgb value of the default background color.
+    ///
+    /// If it is not set, the value of entry 0 (black) is assumed.
+    pub fn background_rgb(&self) -> Rgb {
+        self.background.unwrap_or_else(|| self.rgb(0))
+    }
+
+    /// Set (`Some`) or unset (`None`) the color of the cursor.
+    pub fn set_cursor(&mut self, color: Option<Rgb>) {
+        self.cursor = color;
+    }
+
+    /// Get the color of the cursor, if it is set.
+    pub fn cursor(&self) -> Option<Rgb> {
+        self.cursor
+    }
+
+    /// Get the rgb value of the cursor color.
+    ///
+    /// If it is not set, the value of the default foreground color is assumed.
+    pub fn cursor_rgb(&self) -> Rgb {
+        self.cursor.unwrap_or_else(|| self.foreground_rgb())
+    }
+}
+
+/// The default value of the color with the given index in xterm.
+fn xterm_color(index: u8) -> Rgb {
+    const NAMED: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00),
+        (0xcd, 0x00, 0x00),
+        (0x00, 0xcd, 0x00),
+        (0xcd, 0xcd, 0x00),
+        (0x00, 0x00, 0xee),
+        (0xcd, 0x00, 0xcd),
+        (0x00, 0xcd, 0xcd),
+        (0xe5, 0xe5, 0xe5),
+        (0x7f, 0x7f, 0x7f),
+        (0xff, 0x00, 0x00),
+        (0x00, 0xff, 0x00),
+        (0xff, 0xff, 0x00),
+        (0x5c, 0x5c, 0xff),
+        (0xff, 0x00, 0xff),
+        (0x00, 0xff, 0xff),
+        (0xff, 0xff, 0xff),
+    ];
+    match index {
+        0..=15 => {
+            let (r, g, b) = NAMED[index as usize];
+            Rgb { r, g, b }
+        }
+        16..=231 => {
+            let cube_level = |v: u8| if v == 0 { 0 } else { 55 + 40 * v };
+            let i = index - 16;
+            Rgb {
+                r: cube_level(i / 36),
+                g: cube_level((i / 6) % 6),
+                b: cube_level(i % 6),
+            }
+        }
+        _ => {
+            let v = 8 + 10 * (index - 232);
+            Rgb { r: v, g: v, b: v }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Information about (and signals to) the processes running in the terminal.
+//!
+//! Currently, process information is only supported on Linux (using the proc filesystem).
+use libc;
+use nix::errno::Errno;
+use nix::sys::signal;
+use nix::unistd::Pid;
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+
+/// Signals that can be sent to the programs running in the terminal (see
+/// `Terminal::signal_foreground_process_group`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// `SIGHUP`
+    Hangup,
+    /// `SIGINT`, usually sent by the terminal on `^C`
+    Interrupt,
+    /// `SIGQUIT`, usually sent by the terminal on `^\`
+    Quit,
+    /// `SIGTSTP`, usually sent by the terminal on `^Z`
+    TerminalStop,
+    /// `SIGSTOP`
+    Stop,
+    /// `SIGCONT`
+    Continue,
+    /// `SIGTERM`
+    Terminate,
+    /// `SIGKILL`
+    Kill,
+    /// `SIGUSR1`
+    User1,
+    /// `SIGUSR2`
+    User2,
+}
+
+impl Signal {
+    fn to_nix(self) -> signal::Signal {
+        match self {
+            Signal::Hangup => signal::Signal::SIGHUP,
+            Signal::Interrupt => signal::Signal::SIGINT,
+            Signal::Quit => signal::Signal::SIGQUIT,
+            Signal::TerminalStop => signal::Signal::SIGTSTP,
+            Signal::Stop => signal::Signal::SIGSTOP,
+            Signal::Continue => signal::Signal::SIGCONT,
+            Signal::Terminate => signal::Signal::SIGTERM,
+            Signal::Kill => signal::Signal::SIGKILL,
+            Signal::User1 => signal::Signal::SIGUSR1,
+            Signal::User2 => signal::Signal::SIGUSR2,
+        }
+    }
+}
+
+pub fn signal_process_group(pgrp: libc::pid_t, sig: Signal) -> io::Result<()> {
+    // killpg(0, ...) would signal our own process group.
+    if pgrp <= 0 {
+        return Err(io::Error::from_raw_os_error(libc::ESRCH));
+    }
+    // killpg only fails with an errno.
+    signal::killpg(Pid::from_raw(pgrp), sig.to_nix())
+        .map_err(|e| e.as_errno().unwrap_or(Errno::UnknownErrno).into())
+}
+
+#[cfg(target_os = "linux")]
+pub fn command_line(pid: libc::pid_t) -> io::Result<Vec<OsString>> {
+    use std::os::unix::ffi::OsStringExt;
+
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid))?;
+    // The command line of zombies and kernel threads is empty.
+    if cmdline.is_empty() {
+        return Ok(Vec::new());
+    }
+    // Arguments are terminated (not separated) by null bytes, but may be empty themselves.
+    let args = cmdline.strip_suffix(&[0]).unwrap_or(&cmdline);
+    Ok(args
+        .split(|b| *b == 0)
+        .map(|arg| OsString::from_vec(arg.to_vec()))
+        .collect())
+}
+
+#[cfg(target_os = "linux")]
+pub fn working_directory(pid: libc::pid_t) -> io::Result<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/cwd", pid))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn command_line(_: libc::pid_t) -> io::Result<Vec<OsString>> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn working_directory(_: libc::pid_t) -> io::Result<PathBuf> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "process information is not supported on this platform",
+    )
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn own_process() {
+        let pid = std::process::id() as libc::pid_t;
+        let args = std::env::args_os().collect::<Vec<_>>();
+        assert_eq!(command_line(pid).unwrap(), args);
+        assert_eq!(
+            working_directory(pid).unwrap(),
+            std::env::current_dir().unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_arguments() {
+        use std::io::Read;
+        use std::process::{Command, Stdio};
+
+        // The shell reports that it is running (i.e., its command line is set up) and then waits
+        // for its input to be closed.
+        let args = ["sh", "-c", "echo; read line", "", "x", ""];
+        let mut child = Command::new(args[0])
+            .args(&args[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut ready = [0];
+        child
+            .stdout
+            .as_mut()
+            .unwrap()
+            .read_exact(&mut ready)
+            .unwrap();
+        let command_line = command_line(child.id() as libc::pid_t);
+        drop(child.stdin.take());
+        child.wait().unwrap();
+        assert_eq!(command_line.unwrap(), args);
+    }
+}
@@ -166,6 +166,19 @@ impl PTYInput {
             Ok(())
         }
     }
+
+    pub fn foreground_process_group(&self) -> io::Result<libc::pid_t> {
+        let res = {
+            let lock = self.pty.lock().expect("lock pty for tcgetpgrp");
+            unsafe { libc::tcgetpgrp(lock.fd) }
+        };
+
+        if res < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(res)
+        }
+    }
 }
 
 fn open_ptm() -> Result<libc::c_int> {
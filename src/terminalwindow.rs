@@ -3,28 +3,209 @@ use ansi::{Attr, CursorStyle, Handler, TermInfo};
 use unsegen::base::basic_types::*;
 use unsegen::base::Color as UColor;
 use unsegen::base::{
-    BoolModifyMode, Cursor, CursorState, CursorTarget, Style, StyleModifier, StyledGraphemeCluster,
-    Window, WrappingMode, UNBOUNDED_HEIGHT, UNBOUNDED_WIDTH,
+    BoolModifyMode, Cursor, CursorState, CursorTarget, GraphemeCluster, Style, StyleModifier,
+    StyledGraphemeCluster, Window, WrappingMode, UNBOUNDED_HEIGHT, UNBOUNDED_WIDTH,
 };
 use unsegen::input::{OperationResult, Scrollable};
 use unsegen::widget::{Demand, Demand2D, RenderingHints};
 
 use log::warn;
 
+use clipboard::ClipboardPolicy;
+use event::TerminalEvent;
 use index;
+use input::{InputEncoder, MouseEncoding, MouseProtocol, KITTY_SUPPORTED_FLAGS};
+use palette::Palette;
 use std::cmp::{max, min};
+use std::collections::HashMap;
 use std::fmt::Write;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
+
+/// Blinking speed of a cell
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Blink {
+    Slow,
+    Fast,
+}
+
+/// Style of the underline of a cell
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum UnderlineStyle {
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+/// Colors and attributes of a cell as specified by the program running in the terminal.
+///
+/// In contrast to unsegen styles, colors are only resolved when the cell is drawn. This way, the
+/// default foreground and background colors (`NamedColor::Foreground` and
+/// `NamedColor::Background`) are preserved as such (also in the scrollback) and drawn using the
+/// palette (if set) or the default style of the window that the terminal is drawn into.
+/// Attributes that are not set leave the default style of the window unchanged.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct CellStyle {
+    fg: ansi::Color,
+    bg: ansi::Color,
+    bold: bool,
+    italic: bool,
+    reverse: bool,
+    dim: bool,
+    strike: bool,
+    hidden: bool,
+    blink: Option<Blink>,
+    underline: Option<UnderlineStyle>,
+    underline_color: Option<ansi::Color>,
+    // Index into the hyperlinks of the LineBuffer
+    hyperlink: Option<usize>,
+}
+
+impl Default for CellStyle {
+    fn default() -> Self {
+        CellStyle {
+            fg: ansi::Color::Named(ansi::NamedColor::Foreground),
+            bg: ansi::Color::Named(ansi::NamedColor::Background),
+            bold: false,
+            italic: false,
+            reverse: false,
+            dim: false,
+            strike: false,
+            hidden: false,
+            blink: None,
+            underline: None,
+            underline_color: None,
+            hyperlink: None,
+        }
+    }
+}
+
+impl CellStyle {
+    /// The SGR parameters that set up this style (starting from a reset), as reported by DECRQSS.
+    fn sgr_parameters(&self) -> String {
+        let mut params = "0".to_owned();
+        let flags = [
+            (self.bold, "1"),
+            (self.dim, "2"),
+            (self.italic, "3"),
+            (self.blink == Some(Blink::Slow), "5"),
+            (self.blink == Some(Blink::Fast), "6"),
+            (self.reverse, "7"),
+            (self.hidden, "8"),
+            (self.strike, "9"),
+        ];
+        for (set, param) in flags.iter() {
+            if *set {
+                params.push(';');
+                params.push_str(param);
+            }
+        }
+        let underline = match self.underline {
+            Some(UnderlineStyle::Single) => Some("4"),
+            Some(UnderlineStyle::Double) => Some("4:2"),
+            Some(UnderlineStyle::Curly) => Some("4:3"),
+            Some(UnderlineStyle::Dotted) => Some("4:4"),
+            Some(UnderlineStyle::Dashed) => Some("4:5"),
+            None => None,
+        };
+        if let Some(underline) = underline {
+            params.push(';');
+            params.push_str(underline);
+        }
+        push_sgr_color(&mut params, 38, self.fg);
+        push_sgr_color(&mut params, 48, self.bg);
+        if let Some(color) = self.underline_color {
+            push_sgr_color(&mut params, 58, color);
+        }
+        params
+    }
+}
+
+/// Append the SGR parameters that select the color as foreground (`code` = 38), background (48)
+/// or underline (58) color. Default colors are omitted.
+fn push_sgr_color(params: &mut String, code: u8, color: ansi::Color) {
+    let param = match color {
+        // Named colors use the short forms (30-37, 90-97 etc.) if there are any.
+        ansi::Color::Named(c) if (c as usize) < 8 && code != 58 => {
+            format!("{}", code - 8 + c as u8)
+        }
+        ansi::Color::Named(c) if (c as usize) < 16 && code != 58 => {
+            format!("{}", code + 52 + c as u8 - 8)
+        }
+        ansi::Color::Named(c) if (c as usize) < 16 => format!("{};5;{}", code, c as u8),
+        ansi::Color::Named(_) => return,
+        ansi::Color::Indexed(i) => format!("{};5;{}", code, i),
+        ansi::Color::Spec(c) => format!("{};2;{};{};{}", code, c.r, c.g, c.b),
+    };
+    params.push(';');
+    params.push_str(&param);
+}
+
+/// Terminfo capabilities that are reported using XTGETTCAP (with `None` for boolean capabilities).
+const TERMCAPS: &[(&str, Option<&str>)] = &[
+    ("TN", Some("xterm-256color")),
+    ("name", Some("xterm-256color")),
+    ("Co", Some("256")),
+    ("colors", Some("256")),
+    ("RGB", Some("8/8/8")),
+    ("Tc", None),
+    ("Su", None),
+    ("Smulx", Some("\x1b[4:%p1%dm")),
+    (
+        "Setulc",
+        Some("\x1b[58:2::%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%d%;m"),
+    ),
+    ("Ss", Some("\x1b[%p1%d q")),
+    ("Se", Some("\x1b[2 q")),
+    ("BE", Some("\x1b[?2004h")),
+    ("BD", Some("\x1b[?2004l")),
+    ("PS", Some("\x1b[200~")),
+    ("PE", Some("\x1b[201~")),
+];
+
+/// A command that has been entered in a shell, as marked by the shell using OSC 133.
+///
+/// Positions refer to lines of the buffer of the terminal (see `Terminal::text`), where the first
+/// line (including the scrollback) has index 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShellCommand {
+    /// Line at which the prompt starts
+    pub prompt_line: usize,
+    /// Line at which the command (i.e., the input of the user after the prompt) starts
+    pub command_line: Option<usize>,
+    /// Lines of the output of the command (which extends to the end of the buffer while the
+    /// command is running)
+    pub output: Option<Range<usize>>,
+    /// Exit code of the command, once it has finished (if reported by the shell)
+    pub exit_code: Option<i32>,
+    /// Whether the command has finished
+    pub finished: bool,
+}
+
+impl ShellCommand {
+    fn new(prompt_line: usize) -> Self {
+        ShellCommand {
+            prompt_line,
+            command_line: None,
+            output: None,
+            exit_code: None,
+            finished: false,
+        }
+    }
+}
 
 #[derive(Clone)]
 struct Line {
     content: Vec<StyledGraphemeCluster>,
+    styles: Vec<CellStyle>,
 }
 
 impl Line {
     fn empty() -> Self {
         Line {
             content: Vec::new(),
+            styles: Vec::new(),
         }
     }
 
@@ -34,6 +215,7 @@ impl Line {
 
     fn clear(&mut self) {
         self.content.clear();
+        self.styles.clear();
     }
 
     fn height_for_width(&self, width: Width) -> Height {
@@ -55,6 +237,7 @@ impl Line {
         let missing_elements = (x + 1).checked_sub(self.content.len()).unwrap_or(0);
         self.content
             .extend(::std::iter::repeat(StyledGraphemeCluster::default()).take(missing_elements));
+        self.styles.resize(self.content.len(), CellStyle::default());
 
         let element = self
             .content
@@ -63,6 +246,38 @@ impl Line {
         Some(element)
     }
 
+    fn get_cell_for_writing(
+        &mut self,
+        x: ColIndex,
+        style: CellStyle,
+    ) -> Option<&mut StyledGraphemeCluster> {
+        self.get_cell_mut(x)?;
+        let x = x.raw_value() as usize;
+        self.styles[x] = style;
+        self.content.get_mut(x)
+    }
+
+    /// Replace the cells in the given range by blanks of the given style (growing the line if
+    /// necessary).
+    fn erase(&mut self, range: Range<usize>, style: CellStyle) {
+        if range.start >= range.end {
+            return;
+        }
+        if self.content.len() < range.end {
+            self.content
+                .resize(range.end, StyledGraphemeCluster::default());
+            self.styles.resize(range.end, CellStyle::default());
+        }
+        for x in range {
+            self.content[x] = StyledGraphemeCluster::default();
+            self.styles[x] = style;
+        }
+    }
+
+    fn get_style(&self, x: usize) -> CellStyle {
+        self.styles.get(x).cloned().unwrap_or_default()
+    }
+
     fn get_cell(&self, x: ColIndex) -> Option<&StyledGraphemeCluster> {
         if x < 0 {
             return None;
@@ -86,6 +301,21 @@ struct LineBuffer {
     lines: Vec<Line>,
     window_width: Width,
     default_style: Style,
+
+    // Style that cells receive when they are written to
+    pen: CellStyle,
+
+    // Hyperlinks that are referred to by cells (by index), see intern_hyperlink
+    hyperlinks: Vec<Option<ansi::Hyperlink>>,
+    // Indices of the entries of `hyperlinks` that are in use
+    hyperlink_indices: HashMap<ansi::Hyperlink, usize>,
+    // Indices of the entries of `hyperlinks` that can be reused
+    free_hyperlinks: Vec<usize>,
+    // Number of hyperlinks in use after the last call of collect_hyperlinks
+    live_hyperlinks: usize,
+
+    // Commands as marked by the shell (OSC 133), oldest first
+    commands: Vec<ShellCommand>,
 }
 impl LineBuffer {
     pub fn new() -> Self {
@@ -93,9 +323,94 @@ impl LineBuffer {
             lines: Vec::new(),
             window_width: Width::new(0).unwrap(),
             default_style: Style::default(),
+            pen: CellStyle::default(),
+            hyperlinks: Vec::new(),
+            hyperlink_indices: HashMap::new(),
+            free_hyperlinks: Vec::new(),
+            live_hyperlinks: 0,
+            commands: Vec::new(),
         }
     }
 
+    /// Style of erased cells: Only the background color of the pen is kept.
+    fn erase_style(&self) -> CellStyle {
+        CellStyle {
+            bg: self.pen.bg,
+            ..CellStyle::default()
+        }
+    }
+
+    fn set_hyperlink(&mut self, link: Option<ansi::Hyperlink>) {
+        self.pen.hyperlink = link.map(|link| self.intern_hyperlink(link));
+    }
+
+    /// Get the index of the hyperlink (with the same id and URI), adding it if necessary.
+    fn intern_hyperlink(&mut self, link: ansi::Hyperlink) -> usize {
+        const MIN_COLLECTION_THRESHOLD: usize = 64;
+
+        if let Some(index) = self.hyperlink_indices.get(&link) {
+            return *index;
+        }
+        // Collecting whenever the number of links has doubled keeps the amortized cost constant.
+        if self.hyperlink_indices.len() >= max(MIN_COLLECTION_THRESHOLD, 2 * self.live_hyperlinks) {
+            self.collect_hyperlinks();
+        }
+        let index = match self.free_hyperlinks.pop() {
+            Some(index) => {
+                self.hyperlinks[index] = Some(link.clone());
+                index
+            }
+            None => {
+                self.hyperlinks.push(Some(link.clone()));
+                self.hyperlinks.len() - 1
+            }
+        };
+        self.hyperlink_indices.insert(link, index);
+        index
+    }
+
+    /// Free the hyperlinks that are not referred to by any cell (or the pen) anymore.
+    fn collect_hyperlinks(&mut self) {
+        let mut used = vec![false; self.hyperlinks.len()];
+        let styles = self.lines.iter().flat_map(|line| line.styles.iter());
+        for style in styles.chain(Some(&self.pen)) {
+            if let Some(index) = style.hyperlink {
+                used[index] = true;
+            }
+        }
+        for (index, used) in used.into_iter().enumerate() {
+            if used {
+                continue;
+            }
+            if let Some(link) = self.hyperlinks[index].take() {
+                self.hyperlink_indices.remove(&link);
+                self.free_hyperlinks.push(index);
+            }
+        }
+        self.live_hyperlinks = self.hyperlink_indices.len();
+    }
+
+    fn hyperlink(&self, index: usize) -> Option<&ansi::Hyperlink> {
+        self.hyperlinks.get(index)?.as_ref()
+    }
+
+    fn line_mut(&mut self, y: RowIndex) -> Option<&mut Line> {
+        if y < 0 {
+            return None;
+        }
+        let y = y.raw_value() as usize;
+        // Grow vertically to desired position
+        let missing_elements = (y + 1).checked_sub(self.lines.len()).unwrap_or(0);
+        self.lines
+            .extend(::std::iter::repeat(Line::empty()).take(missing_elements));
+
+        Some(
+            self.lines
+                .get_mut(y)
+                .expect("line existence assured previously"),
+        )
+    }
+
     fn height_as_displayed(&self) -> Height {
         self.lines
             .iter()
@@ -119,21 +434,10 @@ impl CursorTarget for LineBuffer {
         Height::new(UNBOUNDED_HEIGHT).unwrap()
     }
     fn get_cell_mut(&mut self, x: ColIndex, y: RowIndex) -> Option<&mut StyledGraphemeCluster> {
-        if y < 0 {
-            return None;
-        }
-        let y = y.raw_value() as usize;
-        // Grow vertically to desired position
-        let missing_elements = (y + 1).checked_sub(self.lines.len()).unwrap_or(0);
-        self.lines
-            .extend(::std::iter::repeat(Line::empty()).take(missing_elements));
-
-        let line = self
-            .lines
-            .get_mut(y)
-            .expect("line existence assured previously");
-
-        line.get_cell_mut(x)
+        // Cells are only requested mutably by the cursor in order to write text to them, so they
+        // get the current style. (Erasing does not use the cursor, see TerminalWindow::erase.)
+        let pen = self.pen;
+        self.line_mut(y)?.get_cell_for_writing(x, pen)
     }
     fn get_cell(&self, x: ColIndex, y: RowIndex) -> Option<&StyledGraphemeCluster> {
         /*
@@ -170,6 +474,9 @@ pub struct TerminalWindow {
     scrolling_region_end: Option<index::Line>,
     cursor_style: CursorStyle,
 
+    // Stack of flags of the kitty keyboard protocol (the top of which is in effect)
+    keyboard_modes: Vec<u8>,
+
     // Terminal state
     show_cursor: bool,
 }
@@ -186,6 +493,7 @@ impl TerminalWindow {
             scrolling_region_begin: index::Line(1),
             scrolling_region_end: None,
             cursor_style: CursorStyle::Block,
+            keyboard_modes: Vec::new(),
 
             show_cursor: true,
         }
@@ -202,6 +510,11 @@ impl TerminalWindow {
         self.show_cursor = show;
     }
 
+    #[cfg(test)]
+    pub fn hyperlink_capacity(&self) -> usize {
+        self.buffer.hyperlinks.len()
+    }
+
     pub fn set_width(&mut self, w: Width) {
         self.window_width = w;
         self.buffer.set_window_width(w);
@@ -228,6 +541,16 @@ impl TerminalWindow {
         res
     }
 
+    /// Erase the given columns of the line of the cursor (see LineBuffer::erase_style).
+    fn erase(&mut self, range: Range<ColIndex>) {
+        let (start, end) = (range.start.positive_or_zero(), range.end.positive_or_zero());
+        let row = self.with_cursor(|cursor| cursor.get_row());
+        let style = self.buffer.erase_style();
+        if let Some(line) = self.buffer.line_mut(row) {
+            line.erase(start.raw_value() as usize..end.raw_value() as usize, style);
+        }
+    }
+
     fn line_to_buffer_pos_y(&self, line: index::Line) -> RowIndex {
         RowIndex::new(
             max(
@@ -250,31 +573,10 @@ impl TerminalWindow {
         }
     }
 
-    pub fn draw(&mut self, mut window: Window, _: RenderingHints) {
-        let cursor_style_mod = match self.cursor_style {
-            CursorStyle::Beam => {
-                // TODO: not sure how to emulate a beam...
-                StyleModifier::new().underline(BoolModifyMode::Toggle)
-            }
-            CursorStyle::Block => StyleModifier::new().invert(BoolModifyMode::Toggle),
-            CursorStyle::Underline => StyleModifier::new().underline(BoolModifyMode::Toggle),
-        };
-        //temporarily change buffer to show cursor:
-        if self.show_cursor {
-            self.with_cursor(|cursor| {
-                if let Some(cell) = cursor.get_current_cell_mut() {
-                    cursor_style_mod.modify(&mut cell.style);
-                }
-            });
-        }
-
-        let height = window.get_height();
-        let width = window.get_width();
-
-        if height == 0 || width == 0 || self.buffer.lines.is_empty() {
-            return;
-        }
-
+    /// Determine the first line of the buffer that is (at least partially) displayed in a window
+    /// of the given size and the row at which it is drawn (which may be negative if the line is
+    /// wrapped).
+    fn layout(&self, width: Width, height: Height) -> (usize, RowIndex) {
         let scrollback_offset =
             -(self.current_scrollback_pos() - self.buffer.height_as_displayed());
         let minimum_y_start = scrollback_offset + height;
@@ -284,83 +586,414 @@ impl TerminalWindow {
             .len()
             .checked_sub(minimum_y_start.raw_value() as usize)
             .unwrap_or(0);
-        let line_range = start_line..;
         let y_start: RowIndex = min(
             RowIndex::new(0),
             minimum_y_start
-                - self.buffer.lines[line_range.clone()]
+                - self.buffer.lines[start_line..]
                     .iter()
                     .map(|line| line.height_for_width(width))
                     .sum::<Height>(),
         );
+        (start_line, y_start)
+    }
+
+    /// Record a mark of shell integration at the current cursor position.
+    fn set_prompt_mark(&mut self, mark: ansi::PromptMark) {
+        let (x, y) = self.with_cursor(|cursor| cursor.get_position());
+        let line = y.raw_value().max(0) as usize;
+        let commands = &mut self.buffer.commands;
+        let unfinished = commands.last_mut().filter(|command| !command.finished);
+        match (mark, unfinished) {
+            (ansi::PromptMark::PromptStart, unfinished) => {
+                // The shell did not tell us that the previous command has finished.
+                if let Some(command) = unfinished {
+                    if let Some(ref mut output) = command.output {
+                        output.end = line;
+                    }
+                    command.finished = true;
+                }
+                commands.push(ShellCommand::new(line));
+            }
+            (ansi::PromptMark::CommandStart, Some(command)) => command.command_line = Some(line),
+            (ansi::PromptMark::OutputStart, Some(command)) => command.output = Some(line..line),
+            (ansi::PromptMark::CommandFinished(exit_code), Some(command)) => {
+                // The mark is usually placed at the beginning of the line after the output.
+                let end = if x > 0 { line + 1 } else { line };
+                if let Some(ref mut output) = command.output {
+                    output.end = end.max(output.start);
+                }
+                command.exit_code = exit_code;
+                command.finished = true;
+            }
+            (mark, None) => warn!("Ignoring {:?} without prompt", mark),
+        }
+    }
+
+    /// Get the commands that have been marked by the shell (OSC 133), oldest first.
+    pub fn commands(&self) -> Vec<ShellCommand> {
+        let num_lines = self.buffer.lines.len();
+        self.buffer
+            .commands
+            .iter()
+            .map(|command| {
+                let mut command = command.clone();
+                if !command.finished {
+                    if let Some(ref mut output) = command.output {
+                        output.end = num_lines.max(output.start);
+                    }
+                }
+                command
+            })
+            .collect()
+    }
+
+    /// Get the text of the given lines of the buffer (without trailing whitespace).
+    pub fn text(&self, lines: Range<usize>) -> String {
+        let end = lines.end.min(self.buffer.lines.len());
+        let start = lines.start.min(end);
+        self.buffer.lines[start..end]
+            .iter()
+            .map(|line| {
+                line.content
+                    .iter()
+                    .map(|cell| cell.grapheme_cluster.as_str())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Get the (displayed) row at which the given line of the buffer starts.
+    fn display_row_of_line(&self, line: usize) -> i32 {
+        self.buffer.lines[..line.min(self.buffer.lines.len())]
+            .iter()
+            .map(|l| l.height_for_width(self.window_width).raw_value())
+            .sum()
+    }
+
+    /// Scroll such that the given (displayed) row is at the top of the window, if possible.
+    fn scroll_to_top_row(&mut self, row: i32) -> OperationResult {
+        let total = self.buffer.height_as_displayed().raw_value();
+        let height = self.window_height.raw_value();
+        let bottom = (row + height).max(height);
+        let position = if bottom < total {
+            Some(RowIndex::new(bottom))
+        } else {
+            None
+        };
+        if position == self.scrollback_position {
+            return Err(());
+        }
+        self.scrollback_position = position;
+        Ok(())
+    }
+
+    /// Scroll to the closest prompt (see `commands`) above the top of the window.
+    pub fn scroll_to_previous_prompt(&mut self) -> OperationResult {
+        let top = (self.current_scrollback_pos() - self.window_height).raw_value();
+        let row = self
+            .buffer
+            .commands
+            .iter()
+            .rev()
+            .map(|command| self.display_row_of_line(command.prompt_line))
+            .find(|row| *row < top)
+            .ok_or(())?;
+        self.scroll_to_top_row(row)
+    }
+
+    /// Scroll to the closest prompt (see `commands`) below the top of the window.
+    pub fn scroll_to_next_prompt(&mut self) -> OperationResult {
+        let top = (self.current_scrollback_pos() - self.window_height).raw_value();
+        let row = self
+            .buffer
+            .commands
+            .iter()
+            .map(|command| self.display_row_of_line(command.prompt_line))
+            .find(|row| *row > top)
+            .ok_or(())?;
+        self.scroll_to_top_row(row)
+    }
+
+    /// Get the hyperlink of the cell that is drawn at the given position of the window.
+    pub fn hyperlink_at(&self, x: ColIndex, y: RowIndex) -> Option<&ansi::Hyperlink> {
+        let width = self.window_width;
+        let height = self.window_height;
+        if x < 0 || y < 0 || x >= width.from_origin() || y >= height.from_origin() {
+            return None;
+        }
+        let (start_line, mut line_y) = self.layout(width, height);
+        for line in &self.buffer.lines[start_line..] {
+            let line_height = line.height_for_width(width);
+            if y < line_y + line_height {
+                let row = (y - line_y).raw_value();
+                let index = row * width.raw_value() + x.raw_value();
+                let link = line.get_style(index as usize).hyperlink?;
+                return self.buffer.hyperlink(link);
+            }
+            line_y += line_height;
+        }
+        None
+    }
+
+    pub fn draw(&mut self, mut window: Window, _: RenderingHints, appearance: &Appearance) {
+        let palette = &appearance.palette;
+        let cursor_style_mod = match (self.cursor_style, palette.cursor()) {
+            // A hollow block is not possible, so we use an underline to indicate the position of
+            // the cursor when the terminal is not focused.
+            (CursorStyle::Block, _) if !appearance.focused => {
+                StyleModifier::new().underline(BoolModifyMode::Toggle)
+            }
+            (CursorStyle::Beam, _) => {
+                // TODO: not sure how to emulate a beam...
+                StyleModifier::new().underline(BoolModifyMode::Toggle)
+            }
+            (CursorStyle::Block, None) => StyleModifier::new().invert(BoolModifyMode::Toggle),
+            (CursorStyle::Block, Some(color)) => {
+                let style_mod = StyleModifier::new().bg_color(rgb_to_unsegen_color(color));
+                match ansi_to_unsegen_color(
+                    ansi::Color::Named(ansi::NamedColor::CursorText),
+                    palette,
+                ) {
+                    Some(text_color) => style_mod.fg_color(text_color),
+                    None => style_mod,
+                }
+            }
+            (CursorStyle::Underline, _) => StyleModifier::new().underline(BoolModifyMode::Toggle),
+        };
+        let cursor_pos = if self.show_cursor {
+            let (x, y) = self.with_cursor(|cursor| cursor.get_position());
+            // Make sure that the cell under the cursor exists so that the cursor can be drawn.
+            if let Some(line) = self.buffer.line_mut(y) {
+                line.get_cell_mut(x);
+            }
+            Some((x, y))
+        } else {
+            None
+        };
+
+        // Cells are only drawn where there is content, so we have to take care of the remaining
+        // space if the background color differs from that of the host terminal.
+        if let Some(bg) = palette.background() {
+            window.modify_default_style(StyleModifier::new().bg_color(rgb_to_unsegen_color(bg)));
+            window.clear();
+        }
+
+        let height = window.get_height();
+        let width = window.get_width();
+        let default_style = *window.default_style();
+
+        if height == 0 || width == 0 || self.buffer.lines.is_empty() {
+            return;
+        }
+
+        let (start_line, y_start) = self.layout(width, height);
         let mut cursor = Cursor::new(&mut window)
             .position(ColIndex::new(0), y_start)
             .wrapping_mode(WrappingMode::Wrap);
-        for line in self.buffer.lines[line_range].iter() {
-            cursor.write_preformatted(line.content.as_slice());
+        for (y, line) in self.buffer.lines.iter().enumerate().skip(start_line) {
+            let mut content = line
+                .content
+                .iter()
+                .enumerate()
+                .map(|(x, cell)| resolve_cell(cell, line.get_style(x), appearance, default_style))
+                .collect::<Vec<_>>();
+            if let Some((cursor_x, cursor_y)) = cursor_pos {
+                if cursor_y == y as i32 && cursor_x >= 0 {
+                    if let Some(cell) = content.get_mut(cursor_x.raw_value() as usize) {
+                        cursor_style_mod.modify(&mut cell.style);
+                    }
+                }
+            }
+            cursor.write_preformatted(content.as_slice());
             cursor.wrap_line();
         }
+    }
+}
 
-        //revert cursor change
-        if self.show_cursor {
-            self.with_cursor(|cursor| {
-                if let Some(cell) = cursor.get_current_cell_mut() {
-                    cursor_style_mod.modify(&mut cell.style);
-                }
-            });
+/// Create the cell that will actually be drawn from a cell of the buffer and its style.
+///
+/// Everything that is not specified by the style of the cell is taken from `default_style`.
+fn resolve_cell(
+    cell: &StyledGraphemeCluster,
+    style: CellStyle,
+    appearance: &Appearance,
+    default_style: Style,
+) -> StyledGraphemeCluster {
+    let palette = &appearance.palette;
+    let set_if = |on: bool| {
+        if on {
+            BoolModifyMode::True
+        } else {
+            BoolModifyMode::LeaveUnchanged
         }
+    };
+    let mut style_mod = StyleModifier::new()
+        .bold(set_if(style.bold))
+        .italic(set_if(style.italic))
+        .invert(if style.reverse {
+            BoolModifyMode::Toggle
+        } else {
+            BoolModifyMode::LeaveUnchanged
+        })
+        .underline(set_if(
+            style.underline.is_some() || style.hyperlink.is_some(),
+        ));
+
+    // unsegen does not support dim text, so we blend the foreground with the background color.
+    let fg_color = if style.dim {
+        let fg = ansi_to_rgb(style.fg, palette);
+        let bg = ansi_to_rgb(style.bg, palette);
+        let blend = |f: u8, b: u8| ((2 * f as u16 + b as u16) / 3) as u8;
+        Some(UColor::Rgb {
+            r: blend(fg.r, bg.r),
+            g: blend(fg.g, bg.g),
+            b: blend(fg.b, bg.b),
+        })
+    } else {
+        ansi_to_unsegen_color(style.fg, palette)
+    };
+    if let Some(fg_color) = fg_color {
+        style_mod = style_mod.fg_color(fg_color);
+    }
+    if let Some(bg_color) = ansi_to_unsegen_color(style.bg, palette) {
+        style_mod = style_mod.bg_color(bg_color);
+    }
+
+    let blinked_out = match style.blink {
+        Some(Blink::Slow) => appearance.blink_phase % 4 >= 2,
+        Some(Blink::Fast) => appearance.blink_phase % 2 == 1,
+        None => false,
+    };
+    let cluster = if (style.hidden || blinked_out) && cell.grapheme_cluster.width() > 0 {
+        // Only the displayed cell is blanked, the buffer retains the content.
+        blank_cluster(cell.grapheme_cluster.width())
+    } else if style.strike && cell.grapheme_cluster.width() > 0 {
+        // unsegen does not support strikethrough text either, so we emulate it using a combining
+        // long stroke overlay.
+        GraphemeCluster::all_from_str(&format!("{}\u{336}", cell.grapheme_cluster.as_str()))
+            .next()
+            .unwrap_or_else(|| cell.grapheme_cluster.clone())
+    } else {
+        cell.grapheme_cluster.clone()
+    };
+    StyledGraphemeCluster::new(cluster, style_mod.apply(default_style))
+}
+
+/// Create a cluster of spaces that has the given width.
+fn blank_cluster(width: usize) -> GraphemeCluster {
+    if width == 1 {
+        GraphemeCluster::space()
+    } else {
+        // Tabs are stored as a single cluster of multiple spaces (see unsegen::Cursor).
+        GraphemeCluster::all_from_str(&" ".repeat(width))
+            .next()
+            .unwrap_or_else(GraphemeCluster::space)
     }
 }
 
-fn ansi_to_unsegen_color(ansi_color: ansi::Color) -> UColor {
+/// Get the rgb value of a color. Colors that are not set in the palette are assumed to have their
+/// xterm default value.
+fn ansi_to_rgb(ansi_color: ansi::Color, palette: &Palette) -> ansi::Rgb {
     match ansi_color {
         ansi::Color::Named(c) => match c {
-            ansi::NamedColor::Black => UColor::Black,
-            ansi::NamedColor::Red => UColor::Red,
-            ansi::NamedColor::Green => UColor::Green,
-            ansi::NamedColor::Yellow => UColor::Yellow,
-            ansi::NamedColor::Blue => UColor::Blue,
-            ansi::NamedColor::Magenta => UColor::Magenta,
-            ansi::NamedColor::Cyan => UColor::Cyan,
-            ansi::NamedColor::White => UColor::White,
-            ansi::NamedColor::BrightBlack => UColor::LightBlack,
-            ansi::NamedColor::BrightRed => UColor::LightRed,
-            ansi::NamedColor::BrightGreen => UColor::LightGreen,
-            ansi::NamedColor::BrightYellow => UColor::LightYellow,
-            ansi::NamedColor::BrightBlue => UColor::LightBlue,
-            ansi::NamedColor::BrightMagenta => UColor::LightMagenta,
-            ansi::NamedColor::BrightCyan => UColor::LightCyan,
-            ansi::NamedColor::BrightWhite => UColor::LightWhite,
-            ansi::NamedColor::Foreground => UColor::White, //??
-            ansi::NamedColor::Background => UColor::Black, //??
-            ansi::NamedColor::CursorText => {
-                // This is kind of tricky to get...
-                UColor::Black
-            }
-            ansi::NamedColor::Cursor => {
-                // This is kind of tricky to get...
-                UColor::Black
-            }
-            // Also not sure what to do here
-            ansi::NamedColor::DimBlack => UColor::Black,
-            ansi::NamedColor::DimRed => UColor::Red,
-            ansi::NamedColor::DimGreen => UColor::Green,
-            ansi::NamedColor::DimYellow => UColor::Yellow,
-            ansi::NamedColor::DimBlue => UColor::Blue,
-            ansi::NamedColor::DimMagenta => UColor::Magenta,
-            ansi::NamedColor::DimCyan => UColor::Cyan,
-            ansi::NamedColor::DimWhite => UColor::White,
+            ansi::NamedColor::Foreground => palette.foreground_rgb(),
+            ansi::NamedColor::Background | ansi::NamedColor::CursorText => palette.background_rgb(),
+            ansi::NamedColor::Cursor => palette.cursor_rgb(),
+            c if c < ansi::NamedColor::Foreground => palette.rgb(c as u8),
+            c => palette.rgb(c.to_bright() as u8),
         },
-        ansi::Color::Spec(c) => UColor::Rgb {
-            r: c.r,
-            g: c.g,
-            b: c.b,
+        ansi::Color::Spec(c) => c,
+        ansi::Color::Indexed(c) => palette.rgb(c),
+    }
+}
+
+/// Get the unsegen color that is used to draw a color. Default colors that are not set in the
+/// palette result in `None`, i.e., the color of the default style of the window should be used.
+fn ansi_to_unsegen_color(ansi_color: ansi::Color, palette: &Palette) -> Option<UColor> {
+    // Entries of the palette that have been set take precedence over the colors of the host
+    // terminal.
+    let indexed = |index: u8, host_color: UColor| {
+        Some(
+            palette
+                .get(index)
+                .map(rgb_to_unsegen_color)
+                .unwrap_or(host_color),
+        )
+    };
+    match ansi_color {
+        ansi::Color::Named(c) => match c {
+            ansi::NamedColor::Black => indexed(0, UColor::Black),
+            ansi::NamedColor::Red => indexed(1, UColor::Red),
+            ansi::NamedColor::Green => indexed(2, UColor::Green),
+            ansi::NamedColor::Yellow => indexed(3, UColor::Yellow),
+            ansi::NamedColor::Blue => indexed(4, UColor::Blue),
+            ansi::NamedColor::Magenta => indexed(5, UColor::Magenta),
+            ansi::NamedColor::Cyan => indexed(6, UColor::Cyan),
+            ansi::NamedColor::White => indexed(7, UColor::White),
+            ansi::NamedColor::BrightBlack => indexed(8, UColor::LightBlack),
+            ansi::NamedColor::BrightRed => indexed(9, UColor::LightRed),
+            ansi::NamedColor::BrightGreen => indexed(10, UColor::LightGreen),
+            ansi::NamedColor::BrightYellow => indexed(11, UColor::LightYellow),
+            ansi::NamedColor::BrightBlue => indexed(12, UColor::LightBlue),
+            ansi::NamedColor::BrightMagenta => indexed(13, UColor::LightMagenta),
+            ansi::NamedColor::BrightCyan => indexed(14, UColor::LightCyan),
+            ansi::NamedColor::BrightWhite => indexed(15, UColor::LightWhite),
+            ansi::NamedColor::Foreground => palette.foreground().map(rgb_to_unsegen_color),
+            ansi::NamedColor::Background => palette.background().map(rgb_to_unsegen_color),
+            ansi::NamedColor::CursorText => palette.background().map(rgb_to_unsegen_color),
+            ansi::NamedColor::Cursor => palette.cursor().map(rgb_to_unsegen_color),
+            // Also not sure what to do here
+            ansi::NamedColor::DimBlack => indexed(0, UColor::Black),
+            ansi::NamedColor::DimRed => indexed(1, UColor::Red),
+            ansi::NamedColor::DimGreen => indexed(2, UColor::Green),
+            ansi::NamedColor::DimYellow => indexed(3, UColor::Yellow),
+            ansi::NamedColor::DimBlue => indexed(4, UColor::Blue),
+            ansi::NamedColor::DimMagenta => indexed(5, UColor::Magenta),
+            ansi::NamedColor::DimCyan => indexed(6, UColor::Cyan),
+            ansi::NamedColor::DimWhite => indexed(7, UColor::White),
         },
-        ansi::Color::Indexed(c) => {
-            //TODO: We might in the future implement a separate color table, but for new we "reuse"
-            //the table of the underlying terminal:
-            UColor::Ansi(c)
-        }
+        ansi::Color::Spec(c) => Some(rgb_to_unsegen_color(c)),
+        ansi::Color::Indexed(c) => indexed(c, UColor::Ansi(c)),
+    }
+}
+
+fn rgb_to_unsegen_color(c: ansi::Rgb) -> UColor {
+    UColor::Rgb {
+        r: c.r,
+        g: c.g,
+        b: c.b,
+    }
+}
+
+/// Get an indexed (or dynamic) color of the palette. See `ansi::Handler::set_color`.
+fn palette_entry(palette: &Palette, index: usize) -> Option<ansi::Rgb> {
+    match index {
+        0..=255 => palette.get(index as u8),
+        i if i == ansi::NamedColor::Foreground as usize => palette.foreground(),
+        i if i == ansi::NamedColor::Background as usize => palette.background(),
+        i if i == ansi::NamedColor::Cursor as usize => palette.cursor(),
+        _ => None,
+    }
+}
+
+fn mouse_protocol(mode: ansi::Mode) -> Option<MouseProtocol> {
+    match mode {
+        ansi::Mode::X10Mouse => Some(MouseProtocol::X10),
+        ansi::Mode::ReportMouseClicks => Some(MouseProtocol::Clicks),
+        ansi::Mode::ReportMouseMotion => Some(MouseProtocol::ButtonMotion),
+        ansi::Mode::ReportAllMouseMotion => Some(MouseProtocol::AnyMotion),
+        _ => None,
+    }
+}
+
+fn mouse_encoding(mode: ansi::Mode) -> MouseEncoding {
+    match mode {
+        ansi::Mode::SgrMouse => MouseEncoding::Sgr,
+        ansi::Mode::UrxvtMouse => MouseEncoding::Urxvt,
+        _ => MouseEncoding::Default,
     }
 }
 
@@ -369,10 +1002,46 @@ enum BufferMode {
     Alternate,
 }
 
+/// State that is shared between main and alternate screen and determines how cells are drawn.
+pub struct Appearance {
+    palette: Palette,
+    blink_phase: u32,
+    focused: bool,
+}
+
 pub struct DualWindow {
     main: TerminalWindow,
     alternate: TerminalWindow,
     mode: BufferMode,
+
+    // The palette as configured by the user. The current one (which may have been altered by the
+    // program running in the terminal) is part of the appearance.
+    default_palette: Palette,
+    appearance: Appearance,
+
+    input_encoder: InputEncoder,
+
+    // As reported by the program running in the terminal (usually the shell).
+    working_directory: Option<ansi::WorkingDirectory>,
+
+    // Size of a cell in pixels (or 0 if unknown) as configured by the user.
+    cell_width_px: u16,
+    cell_height_px: u16,
+
+    titles: Titles,
+    title_stack: Vec<Titles>,
+
+    // Not yet retrieved by the application.
+    events: Vec<TerminalEvent>,
+
+    clipboard_policy: ClipboardPolicy,
+}
+
+/// Window title and icon name as set by the program running in the terminal
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Titles {
+    pub title: String,
+    pub icon_name: String,
 }
 
 impl DualWindow {
@@ -381,6 +1050,173 @@ impl DualWindow {
             main: TerminalWindow::new(),
             alternate: TerminalWindow::new(),
             mode: BufferMode::Main,
+            default_palette: Palette::default(),
+            appearance: Appearance {
+                palette: Palette::default(),
+                blink_phase: 0,
+                focused: true,
+            },
+            input_encoder: InputEncoder::new(),
+            working_directory: None,
+            cell_width_px: 0,
+            cell_height_px: 0,
+            titles: Titles::default(),
+            title_stack: Vec::new(),
+            events: Vec::new(),
+            clipboard_policy: ClipboardPolicy::default(),
+        }
+    }
+
+    pub fn input_encoder(&self) -> &InputEncoder {
+        &self.input_encoder
+    }
+
+    pub fn input_encoder_mut(&mut self) -> &mut InputEncoder {
+        &mut self.input_encoder
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.default_palette = palette;
+        self.appearance.palette = palette;
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.appearance.palette
+    }
+
+    /// Set the size of a cell in pixels (0 if unknown). Returns whether this is a change.
+    pub fn set_cell_pixel_size(&mut self, width: u16, height: u16) -> bool {
+        let changed = (self.cell_width_px, self.cell_height_px) != (width, height);
+        self.cell_width_px = width;
+        self.cell_height_px = height;
+        changed
+    }
+
+    /// Get the size of the window in pixels (0 if unknown) as reported to the program running in
+    /// the terminal.
+    pub fn pixel_size(&self) -> (u16, u16) {
+        let w = self.get_width().raw_value() as u16;
+        let h = self.get_height().raw_value() as u16;
+        (
+            w.saturating_mul(self.cell_width_px),
+            h.saturating_mul(self.cell_height_px),
+        )
+    }
+
+    pub fn set_clipboard_policy(&mut self, policy: ClipboardPolicy) {
+        self.clipboard_policy = policy;
+    }
+
+    pub fn clipboard_policy(&self) -> &ClipboardPolicy {
+        &self.clipboard_policy
+    }
+
+    pub fn push_event(&mut self, event: TerminalEvent) {
+        self.events.push(event);
+    }
+
+    pub fn drain_events(&mut self) -> Vec<TerminalEvent> {
+        ::std::mem::take(&mut self.events)
+    }
+
+    pub fn titles(&self) -> &Titles {
+        &self.titles
+    }
+
+    fn set_titles(&mut self, titles: Titles) {
+        if titles == self.titles {
+            return;
+        }
+        self.events.push(TerminalEvent::TitleChanged {
+            title: titles.title.clone(),
+            icon_name: titles.icon_name.clone(),
+        });
+        self.titles = titles;
+    }
+
+    pub fn working_directory(&self) -> Option<&ansi::WorkingDirectory> {
+        self.working_directory.as_ref()
+    }
+
+    pub fn advance_blink_phase(&mut self) {
+        self.appearance.blink_phase = self.appearance.blink_phase.wrapping_add(1);
+    }
+
+    /// Set whether the terminal has focus. Returns whether this is a change.
+    pub fn set_focused(&mut self, focused: bool) -> bool {
+        let changed = self.appearance.focused != focused;
+        self.appearance.focused = focused;
+        changed
+    }
+
+    /// Check whether scrolling in the scrollback buffer makes sense, i.e., the program running in
+    /// the terminal does not look like a full screen application (using the alternate screen,
+    /// mouse reporting or application cursor keys).
+    pub fn is_scrollback_useful(&self) -> bool {
+        let modes = &self.input_encoder.modes;
+        match self.mode {
+            BufferMode::Main => modes.mouse_protocol.is_none() && !modes.application_cursor_keys,
+            BufferMode::Alternate => false,
+        }
+    }
+
+    /// The state of the mode as reported by DECRPM.
+    fn mode_state(&self, mode: ansi::Mode) -> ansi::ModeState {
+        let modes = &self.input_encoder.modes;
+        let set = match mode {
+            ansi::Mode::ShowCursor => self.show_cursor,
+            ansi::Mode::SwapScreenAndSetRestoreCursor => matches!(self.mode, BufferMode::Alternate),
+            ansi::Mode::CursorKeys => modes.application_cursor_keys,
+            ansi::Mode::BracketedPaste => modes.bracketed_paste,
+            ansi::Mode::ReportFocusInOut => modes.focus_events,
+            ansi::Mode::X10Mouse
+            | ansi::Mode::ReportMouseClicks
+            | ansi::Mode::ReportMouseMotion
+            | ansi::Mode::ReportAllMouseMotion => modes.mouse_protocol == mouse_protocol(mode),
+            ansi::Mode::SgrMouse | ansi::Mode::UrxvtMouse => {
+                modes.mouse_encoding == mouse_encoding(mode)
+            }
+            // Lines are always wrapped at the end of the window.
+            ansi::Mode::LineWrap => return ansi::ModeState::PermanentlySet,
+            ansi::Mode::DECCOLM
+            | ansi::Mode::Insert
+            | ansi::Mode::Origin
+            | ansi::Mode::BlinkingCursor
+            | ansi::Mode::LineFeedNewLine
+            | ansi::Mode::SynchronizedOutput => return ansi::ModeState::PermanentlyReset,
+        };
+        if set {
+            ansi::ModeState::Set
+        } else {
+            ansi::ModeState::Reset
+        }
+    }
+
+    /// Make the keyboard flags of the current screen take effect.
+    fn update_keyboard_flags(&mut self) {
+        self.input_encoder.modes.keyboard_flags = self.keyboard_modes.last().cloned().unwrap_or(0);
+    }
+
+    fn set_palette_entry(&mut self, index: usize, color: Option<ansi::Rgb>) {
+        match index {
+            0..=255 => self.appearance.palette.set(index as u8, color),
+            i if i == ansi::NamedColor::Foreground as usize => {
+                self.appearance.palette.set_foreground(color)
+            }
+            i if i == ansi::NamedColor::Background as usize => {
+                self.appearance.palette.set_background(color)
+            }
+            i if i == ansi::NamedColor::Cursor as usize => {
+                self.appearance.palette.set_cursor(color)
+            }
+            _ => warn!("Unimplemented: palette entry {}", index),
+        }
+    }
+
+    pub fn draw(&mut self, window: Window, hints: RenderingHints) {
+        match self.mode {
+            BufferMode::Main => self.main.draw(window, hints, &self.appearance),
+            BufferMode::Alternate => self.alternate.draw(window, hints, &self.appearance),
         }
     }
 }
@@ -406,9 +1242,41 @@ impl DerefMut for DualWindow {
 }
 
 impl Handler for DualWindow {
-    /// OSC to set window title
-    fn set_title(&mut self, _: &str) {
-        //TODO: (Although this might not make sense to implement. Do we want to display a title?)
+    /// OSC to set window title and/or icon name
+    fn set_title(&mut self, kind: ansi::TitleKind, title: &str) {
+        let mut titles = self.titles.clone();
+        if kind != ansi::TitleKind::IconName {
+            titles.title = title.to_owned();
+        }
+        if kind != ansi::TitleKind::Title {
+            titles.icon_name = title.to_owned();
+        }
+        self.set_titles(titles);
+    }
+
+    /// Save the window title and/or icon name on the title stack
+    fn push_title(&mut self, _: ansi::TitleKind) {
+        // Like xterm, we limit the size of the stack by evicting the oldest entries.
+        const MAX_TITLES: usize = 10;
+        if self.title_stack.len() >= MAX_TITLES {
+            self.title_stack.remove(0);
+        }
+        // Both are saved, but only the requested ones are restored (see pop_title).
+        self.title_stack.push(self.titles.clone());
+    }
+
+    /// Restore the window title and/or icon name from the title stack
+    fn pop_title(&mut self, kind: ansi::TitleKind) {
+        if let Some(saved) = self.title_stack.pop() {
+            let mut titles = self.titles.clone();
+            if kind != ansi::TitleKind::IconName {
+                titles.title = saved.title;
+            }
+            if kind != ansi::TitleKind::Title {
+                titles.icon_name = saved.icon_name;
+            }
+            self.set_titles(titles);
+        }
     }
 
     /// Set the cursor style
@@ -551,7 +1419,7 @@ impl Handler for DualWindow {
 
     /// Ring the bell
     fn bell(&mut self) {
-        //omitted
+        self.events.push(TerminalEvent::Bell);
     }
 
     /// Substitute char under cursor
@@ -613,12 +1481,12 @@ impl Handler for DualWindow {
     /// Erase means resetting to the default state (default colors, no content,
     /// no mode flags)
     fn erase_chars(&mut self, n: index::Column) {
-        self.with_cursor(|cursor| {
-            let mut c = cursor.save().row().col();
-            for _ in 0..*n {
-                c.write(" ");
-            }
-        });
+        let start = self.with_cursor(|cursor| cursor.get_col());
+        let end = max(
+            start,
+            min(start + n.0 as i32, self.window_width.from_origin()),
+        );
+        self.erase(start..end);
     }
 
     /// Delete `count` chars
@@ -656,17 +1524,13 @@ impl Handler for DualWindow {
 
     /// Clear current line
     fn clear_line(&mut self, mode: ansi::LineClearMode) {
-        self.with_cursor(|cursor| match mode {
-            ansi::LineClearMode::Right => {
-                cursor.clear_line_right();
-            }
-            ansi::LineClearMode::Left => {
-                cursor.clear_line_left();
-            }
-            ansi::LineClearMode::All => {
-                cursor.clear_line();
-            }
-        });
+        let col = self.with_cursor(|cursor| cursor.get_col());
+        let width = self.window_width.from_origin();
+        match mode {
+            ansi::LineClearMode::Right => self.erase(col..width),
+            ansi::LineClearMode::Left => self.erase(ColIndex::new(0)..col + 1),
+            ansi::LineClearMode::All => self.erase(ColIndex::new(0)..width),
+        }
     }
 
     /// Clear screen
@@ -740,60 +1604,44 @@ impl Handler for DualWindow {
 
     /// set a terminal attribute
     fn terminal_attribute(&mut self, attr: Attr) {
-        self.with_cursor(|c| {
-            match attr {
-                Attr::Reset => c.set_style_modifier(StyleModifier::new()),
-                Attr::Bold => {
-                    c.apply_style_modifier(StyleModifier::new().bold(true));
-                }
-                Attr::Dim => {
-                    /* What is this? */
-                    warn!("Unimplemented: attr Dim")
-                }
-                Attr::Italic => {
-                    c.apply_style_modifier(StyleModifier::new().italic(true));
-                }
-                Attr::Underscore => {
-                    c.apply_style_modifier(StyleModifier::new().underline(true));
-                }
-                Attr::BlinkSlow => warn!("Unimplemented: attr BlinkSlow"),
-                Attr::BlinkFast => warn!("Unimplemented: attr BlinkFast"),
-                Attr::Reverse => {
-                    c.apply_style_modifier(StyleModifier::new().invert(true));
-                }
-                Attr::Hidden => warn!("Unimplemented: attr Hidden"),
-                Attr::Strike => warn!("Unimplemented: attr Strike"),
-                Attr::CancelBold => {
-                    c.apply_style_modifier(StyleModifier::new().bold(false));
-                }
-                Attr::CancelBoldDim => {
-                    /*??*/
-                    c.apply_style_modifier(StyleModifier::new().bold(false));
-                }
-                Attr::CancelItalic => {
-                    c.apply_style_modifier(StyleModifier::new().italic(false));
-                }
-                Attr::CancelUnderline => {
-                    c.apply_style_modifier(StyleModifier::new().underline(false));
-                }
-                Attr::CancelBlink => warn!("Unimplemented: attr CancelBlink"),
-                Attr::CancelReverse => {
-                    c.apply_style_modifier(StyleModifier::new().invert(false));
-                }
-                Attr::CancelHidden => warn!("Unimplemented: attr CancelHidden"),
-                Attr::CancelStrike => warn!("Unimplemented: attr CancelStrike"),
-                Attr::Foreground(color) => {
-                    c.apply_style_modifier(
-                        StyleModifier::new().fg_color(ansi_to_unsegen_color(color)),
-                    );
-                }
-                Attr::Background(color) => {
-                    c.apply_style_modifier(
-                        StyleModifier::new().bg_color(ansi_to_unsegen_color(color)),
-                    );
+        // Attributes are only resolved when drawing, see CellStyle.
+        let pen = &mut self.buffer.pen;
+        match attr {
+            // Hyperlinks are not part of the graphic rendition.
+            Attr::Reset => {
+                *pen = CellStyle {
+                    hyperlink: pen.hyperlink,
+                    ..CellStyle::default()
                 }
             }
-        });
+            Attr::Bold => pen.bold = true,
+            Attr::Italic => pen.italic = true,
+            Attr::Reverse => pen.reverse = true,
+            Attr::Dim => pen.dim = true,
+            Attr::BlinkSlow => pen.blink = Some(Blink::Slow),
+            Attr::BlinkFast => pen.blink = Some(Blink::Fast),
+            Attr::Hidden => pen.hidden = true,
+            Attr::Strike => pen.strike = true,
+            Attr::CancelBold => pen.bold = false,
+            Attr::CancelBoldDim => {
+                pen.bold = false;
+                pen.dim = false;
+            }
+            Attr::CancelItalic => pen.italic = false,
+            Attr::CancelReverse => pen.reverse = false,
+            Attr::CancelBlink => pen.blink = None,
+            Attr::CancelHidden => pen.hidden = false,
+            Attr::CancelStrike => pen.strike = false,
+            Attr::Underscore => pen.underline = Some(UnderlineStyle::Single),
+            Attr::DoubleUnderline => pen.underline = Some(UnderlineStyle::Double),
+            Attr::Undercurl => pen.underline = Some(UnderlineStyle::Curly),
+            Attr::DottedUnderline => pen.underline = Some(UnderlineStyle::Dotted),
+            Attr::DashedUnderline => pen.underline = Some(UnderlineStyle::Dashed),
+            Attr::CancelUnderline => pen.underline = None,
+            Attr::UnderlineColor(color) => pen.underline_color = color,
+            Attr::Foreground(color) => pen.fg = color,
+            Attr::Background(color) => pen.bg = color,
+        }
     }
 
     /// Set mode
@@ -802,7 +1650,23 @@ impl Handler for DualWindow {
             ansi::Mode::ShowCursor => {
                 self.show_cursor = true;
             }
-            ansi::Mode::SwapScreenAndSetRestoreCursor => self.mode = BufferMode::Alternate,
+            ansi::Mode::SwapScreenAndSetRestoreCursor => {
+                self.mode = BufferMode::Alternate;
+                self.alternate.keyboard_modes.clear();
+                self.update_keyboard_flags();
+            }
+            ansi::Mode::CursorKeys => self.input_encoder.modes.application_cursor_keys = true,
+            ansi::Mode::BracketedPaste => self.input_encoder.modes.bracketed_paste = true,
+            ansi::Mode::ReportFocusInOut => self.input_encoder.modes.focus_events = true,
+            ansi::Mode::X10Mouse
+            | ansi::Mode::ReportMouseClicks
+            | ansi::Mode::ReportMouseMotion
+            | ansi::Mode::ReportAllMouseMotion => {
+                self.input_encoder.modes.mouse_protocol = mouse_protocol(mode);
+            }
+            ansi::Mode::SgrMouse | ansi::Mode::UrxvtMouse => {
+                self.input_encoder.modes.mouse_encoding = mouse_encoding(mode);
+            }
             _ => {
                 warn!("Unimplemented: set_mode {:?}", mode);
             }
@@ -815,7 +1679,28 @@ impl Handler for DualWindow {
             ansi::Mode::ShowCursor => {
                 self.show_cursor = false;
             }
-            ansi::Mode::SwapScreenAndSetRestoreCursor => self.mode = BufferMode::Main,
+            ansi::Mode::SwapScreenAndSetRestoreCursor => {
+                self.mode = BufferMode::Main;
+                self.update_keyboard_flags();
+            }
+            ansi::Mode::CursorKeys => self.input_encoder.modes.application_cursor_keys = false,
+            ansi::Mode::BracketedPaste => self.input_encoder.modes.bracketed_paste = false,
+            ansi::Mode::ReportFocusInOut => self.input_encoder.modes.focus_events = false,
+            ansi::Mode::X10Mouse
+            | ansi::Mode::ReportMouseClicks
+            | ansi::Mode::ReportMouseMotion
+            | ansi::Mode::ReportAllMouseMotion => {
+                let modes = &mut self.input_encoder.modes;
+                if modes.mouse_protocol == mouse_protocol(mode) {
+                    modes.mouse_protocol = None;
+                }
+            }
+            ansi::Mode::SgrMouse | ansi::Mode::UrxvtMouse => {
+                let modes = &mut self.input_encoder.modes;
+                if modes.mouse_encoding == mouse_encoding(mode) {
+                    modes.mouse_encoding = MouseEncoding::Default;
+                }
+            }
             _ => {
                 warn!("Unimplemented: set_mode {:?}", mode);
             }
@@ -831,14 +1716,12 @@ impl Handler for DualWindow {
 
     /// DECKPAM - Set keypad to applications mode (ESCape instead of digits)
     fn set_keypad_application_mode(&mut self) {
-        //TODO
-        warn!("Unimplemented: set_keypad_application_mode");
+        self.input_encoder.modes.application_keypad = true;
     }
 
     /// DECKPNM - Set keypad to numeric mode (digits intead of ESCape seq)
     fn unset_keypad_application_mode(&mut self) {
-        //TODO
-        warn!("Unimplemented: unset_keypad_application_mode");
+        self.input_encoder.modes.application_keypad = false;
     }
 
     /// Set one of the graphic character sets, G0 to G3, as the active charset.
@@ -860,9 +1743,250 @@ impl Handler for DualWindow {
     }
 
     /// Set an indexed color value
-    fn set_color(&mut self, _: usize, _: ansi::Rgb) {
-        //TODO: Implement this, once there is support for a per-terminal color table
-        warn!("Unimplemented: set_color");
+    fn set_color(&mut self, index: usize, color: ansi::Rgb) {
+        self.set_palette_entry(index, Some(color));
+    }
+
+    /// OSC 8 to start (or end with `None`) a hyperlink for subsequently written cells
+    fn set_hyperlink(&mut self, link: Option<ansi::Hyperlink>) {
+        self.buffer.set_hyperlink(link);
+    }
+
+    /// OSC 52 to set the contents of the given selections
+    fn set_clipboard(&mut self, selections: &[ansi::ClipboardSelection], data: &[u8]) {
+        if !self.clipboard_policy.allow_write {
+            return;
+        }
+        if data.len() > self.clipboard_policy.max_size {
+            warn!("Ignoring clipboard contents of {} bytes", data.len());
+            return;
+        }
+        self.events.push(TerminalEvent::ClipboardSet {
+            selections: selections.to_vec(),
+            contents: String::from_utf8_lossy(data).into_owned(),
+        });
+    }
+
+    /// OSC 52 to query the contents of the given selection
+    fn request_clipboard(&mut self, selection: ansi::ClipboardSelection) {
+        if self.clipboard_policy.allow_read {
+            self.events
+                .push(TerminalEvent::ClipboardRequest { selection });
+        }
+    }
+
+    /// OSC 133 to mark the current position as part of a shell prompt or command
+    fn set_prompt_mark(&mut self, mark: ansi::PromptMark) {
+        self.deref_mut().set_prompt_mark(mark);
+    }
+
+    /// OSC 9 or OSC 777 to show a desktop notification
+    fn notify(&mut self, title: Option<&str>, body: &str) {
+        self.events.push(TerminalEvent::Notification {
+            title: title.map(|title| title.to_owned()),
+            body: body.to_owned(),
+        });
+    }
+
+    /// OSC 7 to report the current working directory (on the given host)
+    fn set_working_directory(&mut self, dir: ansi::WorkingDirectory) {
+        // Shells usually report the directory for every prompt.
+        if self.working_directory.as_ref() != Some(&dir) {
+            self.events
+                .push(TerminalEvent::WorkingDirectoryChanged(dir.clone()));
+            self.working_directory = Some(dir);
+        }
+    }
+
+    /// Reset an indexed color to its default value (or all of the 256 indexed colors, if no index
+    /// is given)
+    fn reset_color(&mut self, index: Option<usize>) {
+        if let Some(index) = index {
+            let default = palette_entry(&self.default_palette, index);
+            self.set_palette_entry(index, default);
+        } else {
+            for index in 0..=255 {
+                self.appearance
+                    .palette
+                    .set(index, self.default_palette.get(index));
+            }
+        }
+    }
+
+    /// Report an indexed (or dynamic) color value
+    fn report_color<W: ::std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        index: usize,
+        terminator: ansi::OscTerminator,
+    ) {
+        let st = terminator.as_str();
+        let palette = &self.appearance.palette;
+        let res = match index {
+            0..=255 => write!(
+                writer,
+                "\x1b]4;{};{}{}",
+                index,
+                ansi::format_rgb(palette.rgb(index as u8)),
+                st
+            ),
+            _ => {
+                let (osc, color) = match index {
+                    i if i == ansi::NamedColor::Foreground as usize => (10, palette.foreground()),
+                    i if i == ansi::NamedColor::Background as usize => (11, palette.background()),
+                    i if i == ansi::NamedColor::Cursor as usize => {
+                        (12, palette.cursor().or_else(|| palette.foreground()))
+                    }
+                    _ => {
+                        warn!("Unimplemented: report_color {}", index);
+                        return;
+                    }
+                };
+                // Unset default colors are those of the host terminal, which we do not know.
+                // Guessing would mislead programs that pick a light or dark theme accordingly.
+                match color {
+                    Some(color) => {
+                        write!(writer, "\x1b]{};{}{}", osc, ansi::format_rgb(color), st)
+                    }
+                    None => return,
+                }
+            }
+        };
+        if let Err(e) = res {
+            warn!("Failed to report color: {}", e);
+        }
+    }
+
+    fn push_keyboard_mode(&mut self, flags: u8) {
+        // Like kitty, we limit the size of the stack by evicting the oldest entries.
+        const MAX_KEYBOARD_MODES: usize = 16;
+        if self.keyboard_modes.len() >= MAX_KEYBOARD_MODES {
+            self.keyboard_modes.remove(0);
+        }
+        self.keyboard_modes.push(flags & KITTY_SUPPORTED_FLAGS);
+        self.update_keyboard_flags();
+    }
+
+    fn pop_keyboard_modes(&mut self, count: usize) {
+        let remaining = self.keyboard_modes.len().saturating_sub(count);
+        self.keyboard_modes.truncate(remaining);
+        self.update_keyboard_flags();
+    }
+
+    fn set_keyboard_mode(&mut self, flags: u8, behavior: ansi::KeyboardModesApplyBehavior) {
+        if self.keyboard_modes.is_empty() {
+            self.keyboard_modes.push(0);
+        }
+        let current = self.keyboard_modes.last_mut().expect("pushed above");
+        *current = match behavior {
+            ansi::KeyboardModesApplyBehavior::Replace => flags,
+            ansi::KeyboardModesApplyBehavior::Union => *current | flags,
+            ansi::KeyboardModesApplyBehavior::Difference => *current & !flags,
+        } & KITTY_SUPPORTED_FLAGS;
+        self.update_keyboard_flags();
+    }
+
+    fn report_keyboard_mode<W: ::std::io::Write>(&mut self, writer: &mut W) {
+        let flags = self.input_encoder.modes.keyboard_flags;
+        if let Err(e) = write!(writer, "\x1b[?{}u", flags) {
+            warn!("Failed to report keyboard mode: {}", e);
+        }
+    }
+
+    fn set_modify_other_keys(&mut self, level: ansi::ModifyOtherKeys) {
+        self.input_encoder.modes.modify_other_keys = level;
+    }
+
+    fn report_window_size<W: ::std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        report: ansi::WindowSizeReport,
+    ) {
+        let res = match report {
+            ansi::WindowSizeReport::TextAreaPixels => {
+                let (w, h) = self.pixel_size();
+                write!(writer, "\x1b[4;{};{}t", h, w)
+            }
+            ansi::WindowSizeReport::CellPixels => write!(
+                writer,
+                "\x1b[6;{};{}t",
+                self.cell_height_px, self.cell_width_px
+            ),
+            ansi::WindowSizeReport::TextAreaCells => write!(
+                writer,
+                "\x1b[8;{};{}t",
+                self.get_height().raw_value(),
+                self.get_width().raw_value()
+            ),
+        };
+        if let Err(e) = res {
+            warn!("Failed to report window size: {}", e);
+        }
+    }
+
+    /// DECRQSS - Report the value of a setting
+    fn report_status_string<W: ::std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        setting: Option<ansi::StatusString>,
+    ) {
+        let value = match setting {
+            Some(ansi::StatusString::GraphicRendition) => {
+                format!("{}m", self.buffer.pen.sgr_parameters())
+            }
+            Some(ansi::StatusString::ScrollingRegion) => match self.scrolling_region_end {
+                Some(end) => format!("{};{}r", self.scrolling_region_begin.0 + 1, end.0),
+                None => format!("1;{}r", self.get_height().raw_value()),
+            },
+            Some(ansi::StatusString::CursorStyle) => {
+                // Blinking is not supported, so the cursor is always steady.
+                let style = match self.cursor_style {
+                    CursorStyle::Block => 2,
+                    CursorStyle::Underline => 4,
+                    CursorStyle::Beam => 6,
+                };
+                format!("{} q", style)
+            }
+            None => {
+                if let Err(e) = write!(writer, "\x1bP0$r\x1b\\") {
+                    warn!("Failed to report status string: {}", e);
+                }
+                return;
+            }
+        };
+        if let Err(e) = write!(writer, "\x1bP1$r{}\x1b\\", value) {
+            warn!("Failed to report status string: {}", e);
+        }
+    }
+
+    /// DECRQM - Report the state of a mode
+    fn report_mode<W: ::std::io::Write>(&mut self, writer: &mut W, private: bool, number: i64) {
+        let state = match ansi::Mode::from_primitive(private, number) {
+            Some(mode) => self.mode_state(mode),
+            None => ansi::ModeState::NotRecognized,
+        };
+        let prefix = if private { "?" } else { "" };
+        if let Err(e) = write!(writer, "\x1b[{}{};{}$y", prefix, number, state as u8) {
+            warn!("Failed to report mode: {}", e);
+        }
+    }
+
+    /// XTGETTCAP - Report the value of a terminfo capability
+    fn report_termcap<W: ::std::io::Write>(&mut self, writer: &mut W, name: &str) {
+        let hex_name = ansi::encode_hex(name.as_bytes());
+        let res = match TERMCAPS.iter().find(|(n, _)| *n == name) {
+            Some((_, Some(value))) => write!(
+                writer,
+                "\x1bP1+r{}={}\x1b\\",
+                hex_name,
+                ansi::encode_hex(value.as_bytes())
+            ),
+            Some((_, None)) => write!(writer, "\x1bP1+r{}\x1b\\", hex_name),
+            None => write!(writer, "\x1bP0+r{}\x1b\\", hex_name),
+        };
+        if let Err(e) = res {
+            warn!("Failed to report terminfo capability: {}", e);
+        }
     }
 
     /// Run the dectest routine